@@ -7,21 +7,29 @@ use ratatui::{
 };
 use std::{
     cmp::{max, min},
+    path::{Path, PathBuf},
     sync::{
         Arc, Mutex,
-        mpsc::{self, Receiver, Sender},
+        mpsc::{self, Receiver, Sender, TryRecvError},
     },
     thread,
     time::Duration,
 };
 
 use crate::{
-    files::Config,
+    duplicates::{self, ContentHashes, HashedFile},
+    files::{self, Config},
     input,
-    mpv::MpvCommand,
-    songs::Songs,
+    lyrics::Lyrics,
+    mpv::{MpvCommand, MpvCommandFeedback},
+    playlist::{self, ExportFormat, Playlist, SmartQuery},
+    scan::{ScanFailure, ScanProgress, ScanReport},
+    songs::{LibraryChanges, Song, Songs},
+    tags::TagEdit,
     widget::{
-        widget_history, widget_library, widget_playing, widget_popup, widget_search, widget_up_next,
+        widget_confirm, widget_duplicates, widget_history, widget_library, widget_lyrics,
+        widget_playing, widget_playlists, widget_popup, widget_prompt, widget_report, widget_scan,
+        widget_search, widget_tag_editor, widget_up_next,
     },
 };
 
@@ -30,7 +38,11 @@ pub enum NavState {
     Player,
     UpNext(TableState),
     Library(TableState),
+    Playlists(TableState),
     Search,
+    Report(TableState),
+    Duplicates(TableState),
+    Lyrics(TableState),
     Exit,
 }
 
@@ -49,10 +61,63 @@ pub struct App {
     pub click_position: Option<Position>,
     pub alert: Option<String>,
     pub song_query: Option<String>,
+    pub playlists: Vec<Playlist>,
+    /// The playlist that [s] adds library songs to, the last one selected in the playlists panel
+    pub playlist_target: usize,
+    pub prompt: Option<Prompt>,
+    pub confirm: Option<Confirm>,
+    pub tag_editor: Option<TagEditor>,
+    /// Groups of library indices of likely copies of the same song, best copy first
+    pub duplicates: Vec<Vec<usize>>,
+    content_hashes: ContentHashes,
+    /// Files being hashed in the background for `duplicates_content_hash`
+    pub hashing: Option<Receiver<Vec<HashedFile>>>,
+    /// Lyrics of the playing song, read when the lyrics pane is opened
+    pub lyrics: Option<Lyrics>,
+    /// The song the lyrics were last read for, so songs without any are not read again
+    lyrics_song_id: Option<u64>,
+    /// Whether the lyrics pane scrolls along with the song, until it is scrolled by hand
+    pub lyrics_follow: bool,
+    /// Progress of the library scan running in the background, if any
+    pub scan: Option<Arc<ScanProgress>>,
+    /// A playlist from `--playlist`, queued once the library scan is done
+    pending_playlist: Option<(Vec<PathBuf>, String)>,
     pub mpris_channel: (Sender<Message>, Receiver<Message>),
     pub needs_redraw: bool,
 }
 
+/// A line of text being typed in a popup, such as the name of a new playlist
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub text: String,
+}
+
+/// A destructive action waiting to be confirmed in a popup
+pub struct Confirm {
+    pub kind: ConfirmKind,
+    pub question: String,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConfirmKind {
+    DeletePlaylist(usize),
+}
+
+/// The tags of a library song being edited in a popup, one text field each
+pub struct TagEditor {
+    /// ID of the song, as its library index changes whenever the library is sorted again
+    pub song_id: u64,
+    pub fields: [String; 5],
+    pub focused: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PromptKind {
+    NewPlaylist,
+    SaveSearch,
+    RenamePlaylist(usize),
+}
+
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Message {
     None,
@@ -75,6 +140,29 @@ pub enum Message {
     ModifyFind(Option<char>),
     ClearUpNext,
     SongSeek(i32),
+    ShowReport,
+    ShowDuplicates,
+    ShowLyrics,
+    FollowLyrics,
+    ToggleHidden(usize),
+    HideLesserCopies,
+    CycleSortOrder,
+    NewPlaylist,
+    NewSmartPlaylist,
+    CycleSmartOrder(usize),
+    CycleSmartLimit(usize),
+    RenamePlaylist(usize),
+    DeletePlaylist(usize),
+    ReplaceUpNext(usize),
+    AddToPlaylist,
+    Export(ExportFormat),
+    ModifyPrompt(Option<char>),
+    SubmitPrompt,
+    SubmitConfirm,
+    EditTags,
+    ModifyTag(Option<char>),
+    CycleTagField(bool),
+    SaveTags,
     Resize,
 }
 
@@ -82,11 +170,71 @@ pub struct AppLayout<'a> {
     border: Block<'a>,
     left_top: Rect,
     left_middle: Rect,
+    left_lower: Rect,
     left_bottom: Rect,
     right_top: Rect,
     right_bottom: Rect,
 }
 
+impl PromptKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            PromptKind::NewPlaylist => " New Playlist ",
+            PromptKind::SaveSearch => " New Smart Playlist ",
+            PromptKind::RenamePlaylist(_) => " Rename Playlist ",
+        }
+    }
+}
+
+impl TagEditor {
+    pub const LABELS: [&'static str; 5] = ["Title", "Artist", "Album", "Genres", "Track"];
+
+    /// Placeholders like "Unknown" start out empty so they are not saved as tags, while values
+    /// inferred from the path are kept to be saved
+    fn new(song: &Song) -> TagEditor {
+        let (missing, inferred) = (song.missing, song.inferred);
+        let field =
+            |value: String, placeholder: bool| if placeholder { String::new() } else { value };
+
+        TagEditor {
+            song_id: song.id,
+            fields: [
+                field(song.title.clone(), missing.title && !inferred.title),
+                field(song.artist.clone(), missing.artist && !inferred.artist),
+                field(song.album.clone(), missing.album && !inferred.album),
+                field(song.genres.join("; "), missing.genres && !inferred.genres),
+                field(song.track.clone(), missing.track && !inferred.track),
+            ],
+            focused: 0,
+        }
+    }
+
+    /// Genres are separated by ';', and an empty track removes the track number
+    fn to_edit(&self) -> Result<TagEdit> {
+        let [title, artist, album, genres, track] = &self.fields;
+        let track = match track.trim() {
+            "" => None,
+            track => Some(
+                track
+                    .parse::<u32>()
+                    .map_err(|_| Error::msg("The track number must be a whole number"))?,
+            ),
+        };
+
+        Ok(TagEdit {
+            title: title.trim().to_owned(),
+            artist: artist.trim().to_owned(),
+            album: album.trim().to_owned(),
+            genres: genres
+                .split(';')
+                .map(|genre| genre.trim().to_owned())
+                .filter(|genre| !genre.is_empty())
+                .collect(),
+            track,
+        })
+    }
+}
+
 impl Message {
     pub fn is_none(&self) -> bool {
         *self == Self::None
@@ -99,7 +247,13 @@ impl NavState {
     }
 
     pub fn event_list_up(&mut self, is_single: bool, num_entries: usize) {
-        if let NavState::UpNext(state) | NavState::Library(state) = self {
+        if let NavState::UpNext(state)
+        | NavState::Library(state)
+        | NavState::Playlists(state)
+        | NavState::Report(state)
+        | NavState::Duplicates(state)
+        | NavState::Lyrics(state) = self
+        {
             let skips = Self::rows_per_skip(is_single);
 
             if num_entries > 0 {
@@ -111,7 +265,13 @@ impl NavState {
     }
 
     pub fn event_list_down(&mut self, is_single: bool, num_entries: usize) {
-        if let NavState::UpNext(state) | NavState::Library(state) = self {
+        if let NavState::UpNext(state)
+        | NavState::Library(state)
+        | NavState::Playlists(state)
+        | NavState::Report(state)
+        | NavState::Duplicates(state)
+        | NavState::Lyrics(state) = self
+        {
             let skips = Self::rows_per_skip(is_single);
 
             if num_entries > 0 {
@@ -140,7 +300,32 @@ impl NavState {
                     NavState::Library(TableState::default())
                 }
             }
+            NavState::Playlists(_) => {
+                if !app.playlists.is_empty() {
+                    NavState::Playlists(
+                        TableState::default()
+                            .with_selected(Some(app.playlist_target.min(app.playlists.len() - 1))),
+                    )
+                } else {
+                    NavState::Playlists(TableState::default())
+                }
+            }
             NavState::Search => NavState::Search,
+            NavState::Report(_) => {
+                if !app.songs.report.is_empty() {
+                    NavState::Report(TableState::default().with_selected(Some(0)))
+                } else {
+                    NavState::Report(TableState::default())
+                }
+            }
+            NavState::Duplicates(_) => {
+                if !app.duplicates.is_empty() {
+                    NavState::Duplicates(TableState::default().with_selected(Some(0)))
+                } else {
+                    NavState::Duplicates(TableState::default())
+                }
+            }
+            NavState::Lyrics(_) => NavState::Lyrics(TableState::default()),
             NavState::Exit => NavState::Exit,
         }
     }
@@ -161,9 +346,10 @@ impl<'a> AppLayout<'a> {
         let [right_top, right_bottom] =
             Layout::vertical([Constraint::Length(3), Constraint::Fill(1)]).areas(right);
 
-        let [left_top, left_middle, left_bottom] = Layout::vertical([
-            Constraint::Length(5),
+        let [left_top, left_middle, left_lower, left_bottom] = Layout::vertical([
+            Constraint::Length(7),
             Constraint::Fill(1),
+            Constraint::Length(8),
             Constraint::Length(3),
         ])
         .areas(left);
@@ -172,6 +358,7 @@ impl<'a> AppLayout<'a> {
             border,
             left_top,
             left_middle,
+            left_lower,
             left_bottom,
             right_top,
             right_bottom,
@@ -181,17 +368,122 @@ impl<'a> AppLayout<'a> {
 
 impl App {
     pub fn new(songs: Songs, config: Config) -> App {
-        App {
+        let (playlists, playlists_alert) = match Playlist::load_all() {
+            Ok(playlists) => (playlists, None),
+            Err(err) => (
+                Vec::new(),
+                Some(format!("Could not load playlists: {}", err)),
+            ),
+        };
+
+        let mut app = App {
+            alert: playlists_alert.or(songs.report.summary()),
             songs,
             nav_state: NavState::Player,
             song_state: SongLoadingState::Forward,
             config,
             paused: false,
             click_position: None,
-            alert: None,
             song_query: None,
+            playlists,
+            playlist_target: 0,
+            prompt: None,
+            confirm: None,
+            tag_editor: None,
+            duplicates: Vec::new(),
+            content_hashes: ContentHashes::default(),
+            hashing: None,
+            lyrics: None,
+            lyrics_song_id: None,
+            lyrics_follow: true,
+            scan: None,
+            pending_playlist: None,
             mpris_channel: mpsc::channel(),
             needs_redraw: true,
+        };
+
+        app.refresh_smart_playlists();
+        app
+    }
+
+    /// Queues a playlist given with `--playlist`, listing entries that are not in the library in the
+    /// scan report. The playlist is read right away so that a missing file stops startup, but only
+    /// queued once a running library scan is done, since its songs may not have been read yet
+    pub fn load_playlist(&mut self, playlist_path: &Path) -> Result<()> {
+        let entries = playlist::read(playlist_path)?;
+        let name = playlist_path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or(playlist_path.to_string_lossy())
+            .into_owned();
+
+        if self.scan.is_some() {
+            self.pending_playlist = Some((entries, name));
+        } else {
+            self.queue_paths(&entries, &name);
+        }
+        Ok(())
+    }
+
+    /// Marks a library scan as running, returning the progress for the scan to report to
+    pub fn begin_scan(&mut self) -> Arc<ScanProgress> {
+        let progress = Arc::new(ScanProgress::default());
+        self.scan = Some(progress.clone());
+        progress
+    }
+
+    /// Searches and smart playlists only pick up these songs once the scan is done, as they are
+    /// evaluated over the whole library
+    pub fn add_partial_songs(&mut self, songs: Vec<Song>) {
+        self.songs.add_partial(&self.config, songs);
+        self.needs_redraw = true;
+    }
+
+    pub fn finish_scan(&mut self, result: Result<(Vec<Song>, ScanReport)>) {
+        self.scan = None;
+        self.needs_redraw = true;
+
+        let result = result.and_then(|(songs, report)| {
+            let cache_path = files::cache_path(self.config.binary_cache)?;
+            self.songs
+                .finish_scan(&self.config, songs, report, &cache_path)
+        });
+        self.refresh_library_view();
+
+        if let Err(err) = result {
+            self.alert = Some(format!("Could not scan music library: {}", err));
+            return;
+        }
+
+        self.alert = self.songs.report.summary();
+        if let Some((entries, name)) = self.pending_playlist.take() {
+            self.queue_paths(&entries, &name);
+        }
+    }
+
+    fn queue_paths(&mut self, entries: &[PathBuf], name: &str) {
+        let unmatched = self.songs.push_back_paths(entries);
+
+        self.alert = Some(format!(
+            "Queued {} of {} songs from {}",
+            entries.len() - unmatched.len(),
+            entries.len(),
+            name
+        ));
+
+        if !unmatched.is_empty() {
+            self.songs.report.failures.extend(
+                unmatched
+                    .iter()
+                    .map(|path| ScanFailure::new(path, "Playlist entry is not in the library")),
+            );
+            self.alert = self.alert.take().map(|alert| {
+                format!(
+                    "{}. {} could not be found, press [e] to view them",
+                    alert,
+                    unmatched.len()
+                )
+            });
         }
     }
 
@@ -215,6 +507,8 @@ impl App {
             .handle_message(message)
             .map_err(|err| Error::msg(err.to_string()));
         let result_state = self.handle_song_state();
+        self.receive_content_hashes();
+        self.update_lyrics();
 
         // Need to wait to send mpris update till song state is setup
         match message {
@@ -262,6 +556,28 @@ impl App {
                 self.exit();
                 return Ok(());
             }
+            Message::Escape if self.prompt.is_some() => {
+                self.prompt = None;
+            }
+            Message::Escape if self.confirm.is_some() => {
+                self.confirm = None;
+            }
+            Message::Escape if self.tag_editor.is_some() => {
+                self.tag_editor = None;
+            }
+            Message::Escape
+                if matches!(
+                    self.nav_state,
+                    NavState::Report(_) | NavState::Duplicates(_)
+                ) =>
+            {
+                self.set_nav_state(
+                    NavState::Library(TableState::default()).as_stateful_default(self),
+                );
+            }
+            Message::Escape if matches!(self.nav_state, NavState::Lyrics(_)) => {
+                self.set_nav_state(NavState::Player);
+            }
             Message::Escape => {
                 self.alert = None;
                 self.song_query = None;
@@ -302,10 +618,14 @@ impl App {
                 self.previous_nav_state();
             }
             Message::NavStateInnerNext(is_single, elements) => {
+                self.stop_following_lyrics();
                 self.nav_state.event_list_down(is_single, elements);
+                self.update_playlist_target();
             }
             Message::NavStateInnerPrev(is_single, elements) => {
+                self.stop_following_lyrics();
                 self.nav_state.event_list_up(is_single, elements);
+                self.update_playlist_target();
             }
             Message::DeleteNextUp(selected) => {
                 self.songs.remove_next_up(selected);
@@ -340,15 +660,224 @@ impl App {
             }
             Message::ReloadConfig => {
                 self.config.reload()?;
+                self.songs.apply_config(&self.config);
+                self.refresh_library_view();
+            }
+            Message::ReloadMusic if self.scan.is_some() => {
+                self.alert = Some("The music library is still being scanned".to_owned());
             }
             Message::ReloadMusic => {
                 self.songs.reload(&self.config)?;
+                self.refresh_smart_playlists();
                 self.set_nav_state(self.nav_state.as_stateful_default(self));
                 self.alert = Some(format!(
                     "New music library loaded from {}",
-                    self.config.music_directory().to_string_lossy()
+                    self.config
+                        .music_directories()
+                        .iter()
+                        .map(|dir| dir.to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ));
+
+                if let Some(summary) = self.songs.report.summary() {
+                    self.alert = self
+                        .alert
+                        .take()
+                        .map(|alert| format!("{}. {}", alert, summary));
+                }
+            }
+            Message::CycleSortOrder => {
+                self.config.sort_order = self.config.sort_order.next();
+                self.songs.apply_config(&self.config);
+                self.refresh_library_view();
+            }
+            Message::NewPlaylist => {
+                self.prompt = Some(Prompt {
+                    kind: PromptKind::NewPlaylist,
+                    text: String::new(),
+                });
+            }
+            Message::NewSmartPlaylist => {
+                if self
+                    .song_query
+                    .as_ref()
+                    .is_some_and(|query| !query.is_empty())
+                {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::SaveSearch,
+                        text: String::new(),
+                    });
+                } else {
+                    self.alert = Some(
+                        "Search for songs first, the search is saved as the smart playlist"
+                            .to_owned(),
+                    );
+                }
+            }
+            Message::CycleSmartOrder(selected) => {
+                if let Some(playlist) = self.playlists.get_mut(selected)
+                    && let Some(smart) = playlist.smart.as_mut()
+                {
+                    smart.order = smart.order.next();
+                    playlist.save()?;
+                    self.refresh_smart_playlists();
+                }
+            }
+            Message::CycleSmartLimit(selected) => {
+                if let Some(playlist) = self.playlists.get_mut(selected)
+                    && let Some(smart) = playlist.smart.as_mut()
+                {
+                    smart.limit = smart.next_limit();
+                    playlist.save()?;
+                    self.refresh_smart_playlists();
+                }
+            }
+            Message::RenamePlaylist(selected) => {
+                if let Some(playlist) = self.playlists.get(selected) {
+                    self.prompt = Some(Prompt {
+                        kind: PromptKind::RenamePlaylist(selected),
+                        text: playlist.name.clone(),
+                    });
+                }
+            }
+            Message::DeletePlaylist(selected) => {
+                if let Some(playlist) = self.playlists.get(selected) {
+                    self.confirm = Some(Confirm {
+                        kind: ConfirmKind::DeletePlaylist(selected),
+                        question: format!("Delete playlist {}?", playlist.name),
+                    });
+                }
+            }
+            Message::ReplaceUpNext(selected) => {
+                if let Some(playlist) = self.playlists.get(selected) {
+                    let (paths, name) = (playlist.paths.clone(), playlist.name.clone());
+                    self.songs.clear_up_next();
+                    self.queue_paths(&paths, &name);
+
+                    // Without a song playing, the first song of the playlist is already up next
+                    if self.songs.song_is_running() {
+                        self.songs.kill_current();
+                    }
+                }
+            }
+            Message::AddToPlaylist => {
+                if let NavState::Library(table_state) = &self.nav_state
+                    && let Some(selected) = table_state.selected()
+                {
+                    self.add_to_playlist(selected)?;
+                }
+            }
+            Message::Export(format) => {
+                self.export(format)?;
+            }
+            Message::ModifyPrompt(addition) => {
+                if let Some(prompt) = self.prompt.as_mut() {
+                    match addition {
+                        Some(addition) => prompt.text.push(addition),
+                        None => {
+                            prompt.text.pop();
+                        }
+                    }
+                }
+            }
+            Message::SubmitPrompt => {
+                if let Some(prompt) = self.prompt.take() {
+                    self.submit_prompt(prompt)?;
+                }
+            }
+            Message::SubmitConfirm => {
+                if let Some(confirm) = self.confirm.take() {
+                    self.submit_confirm(confirm.kind)?;
+                }
+            }
+            Message::EditTags => {
+                if let NavState::Library(table_state) = &self.nav_state
+                    && let Some(selected) = table_state.selected()
+                {
+                    let index = self.songs.showing_songs_library.real_index(selected);
+                    if let Some(song) = self.songs.song(index) {
+                        // The tags of a cue track live in its sheet, not in the shared audio file
+                        if song.cue.is_some() {
+                            self.alert = Some(
+                                "Tracks split from a cue sheet are tagged in the cue sheet"
+                                    .to_owned(),
+                            );
+                        } else {
+                            self.tag_editor = Some(TagEditor::new(song));
+                        }
+                    }
+                }
+            }
+            Message::ModifyTag(addition) => {
+                if let Some(editor) = self.tag_editor.as_mut() {
+                    let field = &mut editor.fields[editor.focused];
+                    match addition {
+                        Some(addition) => field.push(addition),
+                        None => {
+                            field.pop();
+                        }
+                    }
+                }
+            }
+            Message::CycleTagField(forward) => {
+                if let Some(editor) = self.tag_editor.as_mut() {
+                    let count = editor.fields.len();
+                    editor.focused = if forward {
+                        (editor.focused + 1) % count
+                    } else {
+                        (editor.focused + count - 1) % count
+                    };
+                }
+            }
+            Message::SaveTags => {
+                if let Some(editor) = self.tag_editor.take() {
+                    self.save_tags(editor)?;
+                }
+            }
+            Message::ShowDuplicates => {
+                self.refresh_duplicates();
+                if self.duplicates.is_empty() && self.hashing.is_none() {
+                    self.alert = Some("No duplicate songs found".to_owned());
+                } else {
+                    self.set_nav_state(
+                        NavState::Duplicates(TableState::default()).as_stateful_default(self),
+                    );
+                }
+            }
+            Message::ToggleHidden(selected) => {
+                if let Some(index) = self.duplicates.iter().flatten().nth(selected).copied()
+                    && let Some(song) = self.songs.song(index)
+                {
+                    let hidden = !self.songs.is_hidden(song);
+                    self.songs.set_hidden(&[index], hidden)?;
+                }
+            }
+            Message::HideLesserCopies => {
+                let lesser: Vec<usize> = self
+                    .duplicates
+                    .iter()
+                    .flat_map(|group| group.iter().skip(1))
+                    .copied()
+                    .collect();
+                self.songs.set_hidden(&lesser, true)?;
+                self.alert = Some(format!(
+                    "Hid {} lesser copies, they will be left out when playing all",
+                    lesser.len()
                 ));
             }
+            Message::ShowLyrics => {
+                self.lyrics_follow = true;
+                self.set_nav_state(NavState::Lyrics(TableState::default()));
+            }
+            Message::FollowLyrics => {
+                self.lyrics_follow = true;
+            }
+            Message::ShowReport => {
+                self.set_nav_state(
+                    NavState::Report(TableState::default()).as_stateful_default(self),
+                );
+            }
             Message::MoveSong => match &self.nav_state {
                 NavState::UpNext(table_state) => {
                     table_state.selected().map(|selected| {
@@ -366,6 +895,15 @@ impl App {
                         self.songs.push_song_back(real_index);
                     };
                 }
+                NavState::Playlists(table_state) => {
+                    if let Some(playlist) = table_state
+                        .selected()
+                        .and_then(|selected| self.playlists.get(selected))
+                    {
+                        let (paths, name) = (playlist.paths.clone(), playlist.name.clone());
+                        self.queue_paths(&paths, &name);
+                    }
+                }
                 _ => {}
             },
         }
@@ -373,6 +911,186 @@ impl App {
         return Ok(());
     }
 
+    fn stop_following_lyrics(&mut self) {
+        if let NavState::Lyrics(_) = self.nav_state
+            && self.lyrics.as_ref().is_some_and(|lyrics| lyrics.synced)
+        {
+            self.lyrics_follow = false;
+        }
+    }
+
+    /// Reads the lyrics of the playing song while the lyrics pane is open, once per song
+    fn update_lyrics(&mut self) {
+        let NavState::Lyrics(_) = self.nav_state else {
+            return;
+        };
+
+        let song = self.songs.current_song();
+        let song_id = song.map(|song| song.id);
+        if self.lyrics_song_id != song_id {
+            self.lyrics = song.and_then(Lyrics::load);
+            self.lyrics_song_id = song_id;
+            self.lyrics_follow = true;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// The line of the lyrics being sung
+    fn current_lyric_line(&self) -> Option<usize> {
+        let lyrics = self.lyrics.as_ref().filter(|lyrics| lyrics.synced)?;
+        match MpvCommand::GetPosition.run() {
+            Ok(MpvCommandFeedback::Int(position)) => lyrics.current_line(position.max(0) as u64),
+            _ => None,
+        }
+    }
+
+    fn update_playlist_target(&mut self) {
+        if let NavState::Playlists(state) = &self.nav_state
+            && let Some(selected) = state.selected()
+        {
+            self.playlist_target = selected;
+        }
+    }
+
+    fn add_to_playlist(&mut self, selected: usize) -> Result<()> {
+        let real_index = self.songs.showing_songs_library.real_index(selected);
+        let Some(song) = self.songs.song(real_index) else {
+            return Ok(());
+        };
+
+        let Some(playlist) = self.playlists.get_mut(self.playlist_target) else {
+            self.alert =
+                Some("Create a playlist first, press [N] in the playlists panel".to_owned());
+            return Ok(());
+        };
+
+        if playlist.smart.is_some() {
+            self.alert = Some(format!(
+                "{} is a smart playlist, its songs come from its search",
+                playlist.name
+            ));
+            return Ok(());
+        }
+
+        // Playlists list files, so a single track of a cue sheet cannot be saved in one
+        if song.cue.is_some() {
+            self.alert =
+                Some("Tracks split from a cue sheet cannot be added to playlists".to_owned());
+            return Ok(());
+        }
+
+        playlist.paths.push(song.path.clone());
+        playlist.save()?;
+        self.alert = Some(format!("Added {} to {}", song.title, playlist.name));
+        Ok(())
+    }
+
+    /// Exports the selected playlist from the playlists panel, and the queue from anywhere else
+    fn export(&mut self, format: ExportFormat) -> Result<()> {
+        let selected_playlist = match &self.nav_state {
+            NavState::Playlists(state) => state
+                .selected()
+                .and_then(|selected| self.playlists.get(selected)),
+            _ => None,
+        };
+        let (name, songs, entries) = match selected_playlist {
+            Some(playlist) => (
+                playlist.name.as_str(),
+                self.songs.songs_at_paths(&playlist.paths),
+                playlist.paths.len(),
+            ),
+            None => {
+                let queue = self.songs.queue();
+                let entries = queue.len();
+                ("Up Next", queue, entries)
+            }
+        };
+
+        let cue_tracks = songs.iter().filter(|song| song.cue.is_some()).count();
+        let missing = entries.saturating_sub(songs.len());
+        let export_path = playlist::export(name, &songs, format, self.config.export_paths)?;
+
+        let mut alert = format!(
+            "Exported {} songs to {}",
+            songs.len() - cue_tracks,
+            export_path.to_string_lossy()
+        );
+        if cue_tracks > 0 {
+            alert.push_str(&format!(
+                ". {} cue sheet tracks were skipped, as they only play part of a file",
+                cue_tracks
+            ));
+        }
+        if missing > 0 {
+            alert.push_str(&format!(". {} missing songs were skipped", missing));
+        }
+        self.alert = Some(alert);
+
+        Ok(())
+    }
+
+    fn submit_prompt(&mut self, prompt: Prompt) -> Result<()> {
+        match prompt.kind {
+            PromptKind::NewPlaylist => {
+                let playlist = Playlist::create(&prompt.text, None, &self.playlists)?;
+                self.alert = Some(format!("Created playlist {}", playlist.name));
+                self.playlists.push(playlist);
+            }
+            PromptKind::SaveSearch => {
+                let smart = SmartQuery {
+                    query: self.song_query.clone().unwrap_or_default(),
+                    limit: None,
+                    order: Default::default(),
+                };
+                let playlist = Playlist::create(&prompt.text, Some(smart), &self.playlists)?;
+                self.alert = Some(format!(
+                    "Created smart playlist {} from the search",
+                    playlist.name
+                ));
+                self.playlists.push(playlist);
+                self.refresh_smart_playlists();
+            }
+            PromptKind::RenamePlaylist(selected) => {
+                if selected >= self.playlists.len() {
+                    return Ok(());
+                }
+
+                // Taken out first so that it does not clash with its own name
+                let mut playlist = self.playlists.remove(selected);
+                let result = playlist.rename(&prompt.text, &self.playlists);
+                self.playlists.push(playlist);
+                result?;
+            }
+        }
+
+        self.playlists
+            .sort_by(|first, second| first.name.cmp(&second.name));
+        if matches!(self.nav_state, NavState::Playlists(_)) {
+            self.set_nav_state(
+                NavState::Playlists(TableState::default()).as_stateful_default(self),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn submit_confirm(&mut self, kind: ConfirmKind) -> Result<()> {
+        match kind {
+            ConfirmKind::DeletePlaylist(selected) => {
+                if selected < self.playlists.len() {
+                    let playlist = self.playlists.remove(selected);
+                    playlist.delete()?;
+                    self.alert = Some(format!("Deleted playlist {}", playlist.name));
+                    self.set_nav_state(
+                        NavState::Playlists(TableState::default()).as_stateful_default(self),
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn handle_song_state(&mut self) -> Result<()> {
         let exists = self.songs.active_exists();
         let running = self.songs.song_is_running();
@@ -388,7 +1106,8 @@ impl App {
             self.songs.next(&self.song_state);
             self.song_state = SongLoadingState::Forward;
             self.paused = false;
-            self.songs.try_play_current_song(self.config.show_cover)?;
+            self.songs
+                .try_play_current_song(self.config.show_cover, self.config.replay_gain)?;
         } else if !running {
             // Nothing playing yet
             match self.song_state {
@@ -396,7 +1115,8 @@ impl App {
                     self.songs.previous();
                 }
                 SongLoadingState::Forward => {
-                    self.songs.try_play_current_song(self.config.show_cover)?;
+                    self.songs
+                        .try_play_current_song(self.config.show_cover, self.config.replay_gain)?;
                 }
             }
 
@@ -406,11 +1126,133 @@ impl App {
         return Ok(());
     }
 
+    pub fn handle_library_changes(&mut self, changes: LibraryChanges) -> Result<()> {
+        let failures = self.songs.report.failures.len();
+        self.songs.apply_changes(&self.config, changes)?;
+
+        if self.songs.report.failures.len() > failures {
+            self.alert = self.songs.report.summary();
+        }
+
+        self.refresh_library_view();
+        Ok(())
+    }
+
+    /// Re-evaluates smart playlists, so that they follow changes to the library. Cue sheet tracks are
+    /// left out, as playlists can only list whole files
+    fn refresh_smart_playlists(&mut self) {
+        for playlist in self.playlists.iter_mut() {
+            if let Some(smart) = &playlist.smart {
+                playlist.paths = self
+                    .songs
+                    .smart_songs(smart)
+                    .into_iter()
+                    .filter(|song| song.cue.is_none())
+                    .map(|song| song.path.clone())
+                    .collect();
+            }
+        }
+    }
+
+    /// Writes the edited tags to the file, then reads just that song again. The editor stays open
+    /// when the tags cannot be written, so nothing typed is lost
+    fn save_tags(&mut self, editor: TagEditor) -> Result<()> {
+        let Some(song) = self.songs.song_by_id(editor.song_id) else {
+            self.alert = Some("The song is no longer in the library".to_owned());
+            return Ok(());
+        };
+        let path = song.path.clone();
+
+        let result = editor.to_edit().and_then(|edit| edit.write(&path));
+        if let Err(err) = result {
+            self.alert = Some(format!("Could not save tags: {}", err));
+            self.tag_editor = Some(editor);
+            return Ok(());
+        }
+
+        self.songs.reload_song(&self.config, editor.song_id)?;
+        self.refresh_library_view();
+        self.alert = Some(format!("Saved tags to {}", path.to_string_lossy()));
+        Ok(())
+    }
+
+    /// Groups duplicates using the file contents hashed so far. Hashing reads every file of the
+    /// library, so files that were not hashed yet are hashed in the background
+    fn refresh_duplicates(&mut self) {
+        let content_hash = self.config.duplicates_content_hash;
+        self.duplicates = self
+            .songs
+            .find_duplicates(content_hash.then_some(&self.content_hashes));
+
+        if let NavState::Duplicates(state) = &mut self.nav_state {
+            let copies = self.duplicates.iter().flatten().count();
+            if state.selected().is_some_and(|selected| selected >= copies) {
+                state.select(copies.checked_sub(1));
+            }
+        }
+
+        if content_hash && self.hashing.is_none() {
+            let missing = self.songs.missing_content_hashes(&self.content_hashes);
+            if !missing.is_empty() {
+                let (sender, receiver) = mpsc::channel();
+                thread::spawn(move || {
+                    let _ = sender.send(duplicates::hash_files(missing));
+                });
+                self.hashing = Some(receiver);
+            }
+        }
+    }
+
+    fn receive_content_hashes(&mut self) {
+        let hashed = match self.hashing.as_ref().map(|receiver| receiver.try_recv()) {
+            Some(Ok(hashed)) => hashed,
+            Some(Err(TryRecvError::Disconnected)) => Vec::new(),
+            Some(Err(TryRecvError::Empty)) | None => return,
+        };
+
+        self.hashing = None;
+        self.content_hashes.insert(hashed);
+        self.needs_redraw = true;
+
+        if let NavState::Duplicates(_) = self.nav_state {
+            self.refresh_duplicates();
+            if self.duplicates.is_empty() && self.hashing.is_none() {
+                self.alert = Some("No duplicate songs found".to_owned());
+            }
+        }
+    }
+
+    /// Keeps the search results and selection valid after the library was replaced
+    fn refresh_library_view(&mut self) {
+        self.refresh_smart_playlists();
+
+        // Duplicate groups hold library indices, which change with the library
+        if let NavState::Duplicates(_) = self.nav_state {
+            self.refresh_duplicates();
+        }
+        if self.song_query.is_some() {
+            self.songs.filter_apply(self.song_query.as_ref());
+        }
+
+        let songs = self.songs.songs_showing_library();
+        if let NavState::Library(state) = &mut self.nav_state
+            && state.selected().is_some_and(|selected| selected >= songs)
+        {
+            state.select(songs.checked_sub(1));
+        }
+
+        self.needs_redraw = true;
+    }
+
     pub fn handle_click(&mut self, layout: &AppLayout) {
         if self.click_position_matches_rect(layout.left_top) {
             self.set_nav_state(NavState::Player);
         } else if self.click_position_matches_rect(layout.left_middle) {
             self.set_nav_state(NavState::UpNext(TableState::default()).as_stateful_default(&self));
+        } else if self.click_position_matches_rect(layout.left_lower) {
+            self.set_nav_state(
+                NavState::Playlists(TableState::default()).as_stateful_default(self),
+            );
         } else if self.click_position_matches_rect(layout.right_bottom) {
             self.set_nav_state(NavState::Library(TableState::default()).as_stateful_default(&self));
         } else if self.click_position_matches_rect(layout.right_top) {
@@ -426,7 +1268,11 @@ impl App {
         let widget_history = widget_history::build(self);
         let mut widget_search = widget_search::build(self);
         let mut widget_next = widget_up_next::build(self, layout.left_middle);
-        let mut widget_library = widget_library::build(self, layout.right_bottom);
+        let (library_start, mut library_state) = match &self.nav_state {
+            NavState::Library(state) => widget_library::window(state, layout.right_bottom),
+            _ => (0, TableState::default()),
+        };
+        let mut widget_library = widget_library::build(self, layout.right_bottom, library_start);
 
         if let NavState::Search = self.nav_state {
             let mut border = Block::bordered()
@@ -450,7 +1296,9 @@ impl App {
                     .border_style(Style::new().fg(self.config.color_border))
                     .border_type(BorderType::Thick)
                     .title_top(" Up Next ")
-                    .title_bottom(" | [Enter] Play Now | [Backspace] Remove | [c] Clear | ")
+                    .title_bottom(
+                        " | [Enter] Play Now | [Backspace] Remove | [c] Clear | [w/W] Export | ",
+                    )
                     .title_alignment(Alignment::Center),
             );
             frame.render_stateful_widget(widget_next, layout.left_middle, state);
@@ -463,20 +1311,52 @@ impl App {
             frame.render_widget(widget_next, layout.left_middle);
         }
 
+        let widget_playlists = widget_playlists::build(self);
+        if let NavState::Playlists(state) = &mut self.nav_state {
+            frame.render_stateful_widget(
+                widget_playlists.block(
+                    Block::bordered()
+                        .border_style(Style::new().fg(self.config.color_border))
+                        .border_type(BorderType::Thick)
+                        .title_top(" Playlists ")
+                        .title_bottom(
+                            " | [Enter] Play Later | [x] Play Now | [N] New | [E] Rename | [d] Delete | [O/L] Order/Limit | [w/W] Export | ",
+                        )
+                        .title_alignment(Alignment::Center),
+                ),
+                layout.left_lower,
+                state,
+            );
+        } else {
+            frame.render_widget(
+                widget_playlists.block(
+                    Block::bordered()
+                        .title_top(" Playlists ")
+                        .title_alignment(Alignment::Center),
+                ),
+                layout.left_lower,
+            );
+        }
+
+        let library_title = match self.scan {
+            Some(_) => format!(" Library ({}, Scanning...) ", self.config.sort_order.name()),
+            None => format!(" Library ({}) ", self.config.sort_order.name()),
+        };
         if let NavState::Library(state) = &mut self.nav_state {
             widget_library = widget_library.block(
                 Block::bordered()
                     .border_style(Style::new().fg(self.config.color_border))
-                    .title(" Library ")
+                    .title(library_title.clone())
                     .border_type(BorderType::Thick)
-                    .title_bottom(" | [/] Search | [Enter] Play Later | [a] Play All | ")
+                    .title_bottom(" | [/] Search | [Enter] Play Later | [a] Play All | [s] Save | [S] Save Search | [o] Sort | ")
                     .title_alignment(Alignment::Center),
             );
-            frame.render_stateful_widget(widget_library, layout.right_bottom, state);
+            frame.render_stateful_widget(widget_library, layout.right_bottom, &mut library_state);
+            *state.offset_mut() = library_start + library_state.offset();
         } else {
             widget_library = widget_library.block(
                 Block::bordered()
-                    .title(" Library ")
+                    .title(library_title.clone())
                     .title_alignment(Alignment::Center),
             );
             frame.render_widget(widget_library, layout.right_bottom);
@@ -511,6 +1391,91 @@ impl App {
         #[cfg(not(feature = "image"))]
         frame.render_widget(&widget_playing, border_player.inner(layout.left_top));
 
+        // The scan progress takes the place of the player until the library is ready
+        if let Some(progress) = &self.scan {
+            frame.render_widget(Clear, layout.left_top);
+            frame.render_widget(widget_scan::build(self, progress), layout.left_top);
+        }
+
+        if let NavState::Report(_) = self.nav_state {
+            let vertical = Layout::vertical([Constraint::Percentage(80)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(80)]).flex(Flex::Center);
+            let [area] = vertical.areas(frame.area());
+            let [area] = horizontal.areas(area);
+            let widget_report = widget_report::build(self, area);
+
+            if let NavState::Report(state) = &mut self.nav_state {
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(widget_report, area, state);
+            }
+        }
+
+        if let NavState::Duplicates(_) = self.nav_state {
+            let vertical = Layout::vertical([Constraint::Percentage(80)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(80)]).flex(Flex::Center);
+            let [area] = vertical.areas(frame.area());
+            let [area] = horizontal.areas(area);
+            let widget_duplicates = widget_duplicates::build(self);
+
+            if let NavState::Duplicates(state) = &mut self.nav_state {
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(widget_duplicates, area, state);
+            }
+        }
+
+        if let NavState::Lyrics(_) = self.nav_state {
+            let vertical = Layout::vertical([Constraint::Percentage(80)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(80)]).flex(Flex::Center);
+            let [area] = vertical.areas(frame.area());
+            let [area] = horizontal.areas(area);
+            let current = self.current_lyric_line();
+            let widget_lyrics = widget_lyrics::build(self, area, current);
+
+            if let NavState::Lyrics(state) = &mut self.nav_state {
+                if self.lyrics_follow {
+                    state.select(current);
+                }
+                frame.render_widget(Clear, area);
+                frame.render_stateful_widget(widget_lyrics, area, state);
+            }
+        }
+
+        if let Some(prompt) = &self.prompt {
+            let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
+            let horizontal =
+                Layout::horizontal([Constraint::Length(max(40, prompt.text.len() as u16 + 4))])
+                    .flex(Flex::Center);
+            let [area] = vertical.areas(frame.area());
+            let [area] = horizontal.areas(area);
+
+            frame.render_widget(Clear, area);
+            frame.render_widget(widget_prompt::build(self, prompt), area);
+        }
+
+        if let Some(editor) = &self.tag_editor {
+            let vertical = Layout::vertical([Constraint::Length(7)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Percentage(60)]).flex(Flex::Center);
+            let [area] = vertical.areas(frame.area());
+            let [area] = horizontal.areas(area);
+
+            frame.render_widget(Clear, area);
+            frame.render_widget(widget_tag_editor::build(self, editor), area);
+        }
+
+        if let Some(confirm) = &self.confirm {
+            let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
+            let horizontal = Layout::horizontal([Constraint::Length(max(
+                30,
+                confirm.question.len() as u16 + 2,
+            ))])
+            .flex(Flex::Center);
+            let [area] = vertical.areas(frame.area());
+            let [area] = horizontal.areas(area);
+
+            frame.render_widget(Clear, area);
+            frame.render_widget(widget_confirm::build(self, confirm), area);
+        }
+
         if let Some(alert) = &self.alert {
             let vertical = Layout::vertical([Constraint::Length(3)]).flex(Flex::Center);
             let horizontal =
@@ -558,12 +1523,19 @@ impl App {
                 self.nav_state = NavState::UpNext(TableState::default()).as_stateful_default(self);
             }
             NavState::UpNext(_) => {
+                self.nav_state =
+                    NavState::Playlists(TableState::default()).as_stateful_default(self);
+            }
+            NavState::Playlists(_) => {
                 self.nav_state = NavState::Library(TableState::default()).as_stateful_default(self);
             }
             NavState::Library(_) => self.nav_state = NavState::Search,
             NavState::Search => {
                 self.nav_state = NavState::Player;
             }
+            NavState::Report(_) | NavState::Duplicates(_) | NavState::Lyrics(_) => {
+                self.nav_state = NavState::Library(TableState::default()).as_stateful_default(self);
+            }
             NavState::Exit => {}
         }
     }
@@ -577,9 +1549,16 @@ impl App {
                 self.nav_state = NavState::Player;
             }
             NavState::Library(_) => {
+                self.nav_state =
+                    NavState::Playlists(TableState::default()).as_stateful_default(self);
+            }
+            NavState::Playlists(_) => {
                 self.nav_state = NavState::UpNext(TableState::default()).as_stateful_default(self);
             }
-            NavState::Search => {
+            NavState::Search
+            | NavState::Report(_)
+            | NavState::Duplicates(_)
+            | NavState::Lyrics(_) => {
                 self.nav_state = NavState::Library(TableState::default()).as_stateful_default(self);
             }
             NavState::Exit => {}
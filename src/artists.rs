@@ -0,0 +1,141 @@
+use std::collections::HashMap;
+
+use crate::files::Config;
+
+/// Splits artist credits like "A feat. B" into the individual artists and normalizes their spelling
+/// based on the configured separators and aliases
+pub struct ArtistRules<'a> {
+    separators: Vec<String>,
+    aliases: HashMap<String, &'a str>,
+}
+
+impl<'a> ArtistRules<'a> {
+    pub fn new(config: &'a Config) -> ArtistRules<'a> {
+        ArtistRules {
+            separators: config
+                .artist_separators
+                .iter()
+                .filter(|separator| !separator.is_empty())
+                .map(|separator| separator.to_ascii_lowercase())
+                .collect(),
+            aliases: config
+                .artist_aliases
+                .iter()
+                .map(|(variant, artist)| (variant.to_lowercase(), artist.as_str()))
+                .collect(),
+        }
+    }
+
+    pub fn split(&self, credit: &str) -> Vec<String> {
+        let mut artists: Vec<String> = Vec::new();
+        let mut rest = credit;
+
+        loop {
+            // ASCII lowercasing keeps byte offsets valid for slicing the original credit
+            let lowercase = rest.to_ascii_lowercase();
+            let next = self
+                .separators
+                .iter()
+                .filter_map(|separator| {
+                    lowercase
+                        .find(separator.as_str())
+                        .map(|start| (start, separator.len()))
+                })
+                .min();
+
+            let (artist, remaining) = match next {
+                Some((start, len)) => (&rest[..start], Some(&rest[start + len..])),
+                None => (rest, None),
+            };
+
+            let artist = self.normalize(artist.trim());
+            if !artist.is_empty() && !artists.contains(&artist) {
+                artists.push(artist);
+            }
+
+            match remaining {
+                Some(remaining) => rest = remaining,
+                None => break,
+            }
+        }
+
+        if artists.is_empty() {
+            artists.push(credit.to_owned());
+        }
+
+        artists
+    }
+
+    pub fn normalize(&self, artist: &str) -> String {
+        self.aliases
+            .get(&artist.to_lowercase())
+            .map(|artist| artist.to_string())
+            .unwrap_or(artist.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(separators: &[&str], aliases: &[(&str, &str)]) -> Config {
+        let mut config = Config::default();
+        config.artist_separators = separators
+            .iter()
+            .map(|separator| separator.to_string())
+            .collect();
+        config.artist_aliases = aliases
+            .iter()
+            .map(|(variant, artist)| (variant.to_string(), artist.to_string()))
+            .collect();
+        config
+    }
+
+    #[test]
+    fn splits_on_default_separators() {
+        let config = Config::default();
+        let rules = ArtistRules::new(&config);
+
+        assert_eq!(rules.split("A feat. B"), ["A", "B"]);
+        assert_eq!(rules.split("A; B ft. C"), ["A", "B", "C"]);
+        assert_eq!(rules.split("A FEATURING B"), ["A", "B"]);
+        assert_eq!(rules.split("A Feat B"), ["A", "B"]);
+        assert_eq!(
+            rules.split("Simon with Garfunkel"),
+            ["Simon with Garfunkel"]
+        );
+    }
+
+    #[test]
+    fn splits_on_configured_separators() {
+        let config = config(&[" with ", " & ", ""], &[]);
+        let rules = ArtistRules::new(&config);
+
+        assert_eq!(rules.split("A WITH B & C"), ["A", "B", "C"]);
+        assert_eq!(rules.split("A feat. B"), ["A feat. B"]);
+    }
+
+    #[test]
+    fn trims_and_deduplicates_artists() {
+        let config = Config::default();
+        let rules = ArtistRules::new(&config);
+
+        assert_eq!(rules.split("  A ;B;  ; A "), ["A", "B"]);
+        assert_eq!(rules.split(" ; "), [" ; "]);
+        assert_eq!(rules.split("Ünïcode feat. B"), ["Ünïcode", "B"]);
+    }
+
+    #[test]
+    fn normalizes_aliases_case_insensitively() {
+        let config = config(
+            &[";"],
+            &[("beatles", "The Beatles"), ("Beatles, The", "The Beatles")],
+        );
+        let rules = ArtistRules::new(&config);
+
+        assert_eq!(rules.normalize("BEATLES"), "The Beatles");
+        assert_eq!(rules.normalize("beatles, the"), "The Beatles");
+        assert_eq!(rules.normalize("Beatle"), "Beatle");
+        assert_eq!(rules.split(" Beatles ; beatles, the"), ["The Beatles"]);
+    }
+}
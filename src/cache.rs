@@ -0,0 +1,275 @@
+use std::{
+    fs::{self, File},
+    io::{BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Error;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{songs::Song, utilities};
+
+/// Bump this and add a step to `LibraryCache::migrate` whenever the cache layout changes
+const CACHE_VERSION: u64 = 5;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct LibraryCache {
+    pub version: u64,
+    pub roots: Vec<PathBuf>,
+    pub scanned_at: u64,
+    #[serde(default)]
+    pub songs: Vec<Song>,
+}
+
+impl LibraryCache {
+    pub fn new(roots: Vec<PathBuf>, songs: Vec<Song>) -> LibraryCache {
+        LibraryCache {
+            version: CACHE_VERSION,
+            roots,
+            scanned_at: utilities::now(),
+            songs,
+        }
+    }
+
+    /// Reads a cache written by any previous version, migrating it to the current layout
+    pub fn read(cache_path: &Path) -> Result<LibraryCache, Error> {
+        let file = File::open(cache_path).map_err(|err| Error::new(err))?;
+
+        if Self::is_binary(cache_path) {
+            return Self::read_binary(BufReader::new(file));
+        }
+
+        let value: Value =
+            serde_json::from_reader(BufReader::new(file)).map_err(|err| Error::new(err))?;
+
+        Self::from_value(Self::migrate(value)?)
+    }
+
+    /// The binary layout is not self-describing, so older binary caches cannot be migrated and are
+    /// rebuilt by a full scan instead
+    fn read_binary(mut reader: impl Read) -> Result<LibraryCache, Error> {
+        let version: u64 = bincode::deserialize_from(&mut reader).map_err(|err| Error::new(err))?;
+
+        if version != CACHE_VERSION {
+            return Err(Error::msg(format!(
+                "Binary cache version {} does not match the supported version {}",
+                version, CACHE_VERSION
+            )));
+        }
+
+        let (roots, scanned_at, songs) =
+            bincode::deserialize_from(reader).map_err(|err| Error::new(err))?;
+
+        Ok(LibraryCache {
+            version,
+            roots,
+            scanned_at,
+            songs,
+        })
+    }
+
+    fn is_binary(cache_path: &Path) -> bool {
+        cache_path
+            .extension()
+            .is_some_and(|extension| extension == "bin")
+    }
+
+    fn migrate(mut value: Value) -> Result<Value, Error> {
+        let mut version = match &value {
+            // Before versioning the cache was a bare list of songs
+            Value::Array(_) => 0,
+            _ => value
+                .get("version")
+                .and_then(|version| version.as_u64())
+                .ok_or(Error::msg("Cache is missing its version"))?,
+        };
+
+        if version > CACHE_VERSION {
+            return Err(Error::msg(format!(
+                "Cache version {} is newer than the supported version {}",
+                version, CACHE_VERSION
+            )));
+        }
+
+        while version < CACHE_VERSION {
+            value = Self::migrate_step(version, value);
+            version += 1;
+        }
+
+        if let Some(envelope) = value.as_object_mut() {
+            envelope.insert("version".to_owned(), Value::from(CACHE_VERSION));
+        }
+
+        Ok(value)
+    }
+
+    /// Migrates from `version` to the one after it
+    fn migrate_step(version: u64, value: Value) -> Value {
+        match version {
+            0 => Self::migrate_v0(value),
+            // Sort names are new
+            1 => Self::reread_songs(value),
+            // Cue sheet fields are new, but default to a song playing its whole file
+            2 => value,
+            // ReplayGain tags are new
+            3 => Self::reread_songs(value),
+            // Placeholders were stored as if they were tags, so missing tags are not known
+            4 => Self::reread_songs(value),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Wraps the bare song list in an envelope. Those songs predate fingerprints and audio
+    /// properties, so their fingerprints are cleared to have them read again on the next scan
+    fn migrate_v0(value: Value) -> Value {
+        let songs = match value {
+            Value::Array(songs) => songs
+                .into_iter()
+                .map(|mut song| {
+                    if let Some(song) = song.as_object_mut() {
+                        song.insert("modified".to_owned(), Value::from(0));
+                        song.insert("size".to_owned(), Value::from(0));
+                    }
+                    song
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut envelope = Map::new();
+        envelope.insert("version".to_owned(), Value::from(1));
+        envelope.insert("roots".to_owned(), Value::Array(Vec::new()));
+        envelope.insert("scanned_at".to_owned(), Value::from(0));
+        envelope.insert("songs".to_owned(), Value::Array(songs));
+        Value::Object(envelope)
+    }
+
+    /// Clears fingerprints so that songs are read again to pick up newly supported tags
+    fn reread_songs(mut value: Value) -> Value {
+        if let Some(Value::Array(songs)) = value.get_mut("songs") {
+            for song in songs.iter_mut().filter_map(|song| song.as_object_mut()) {
+                song.insert("modified".to_owned(), Value::from(0));
+            }
+        }
+
+        value
+    }
+
+    /// Songs that no longer deserialize are dropped so that they get read again from disk,
+    /// instead of discarding the whole cache
+    fn from_value(mut value: Value) -> Result<LibraryCache, Error> {
+        let songs = match value
+            .as_object_mut()
+            .and_then(|envelope| envelope.remove("songs"))
+        {
+            Some(Value::Array(songs)) => songs
+                .into_iter()
+                .filter_map(|song| serde_json::from_value(song).ok())
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        let mut cache: LibraryCache =
+            serde_json::from_value(value).map_err(|err| Error::new(err))?;
+        cache.songs = songs;
+        Ok(cache)
+    }
+
+    /// Writes to a temporary file first, so a crash mid-write never leaves a truncated cache
+    pub fn write(&self, cache_path: &Path) -> Result<(), Error> {
+        let data = if Self::is_binary(cache_path) {
+            bincode::serialize(&(self.version, &self.roots, self.scanned_at, &self.songs))
+                .map_err(|err| Error::new(err))?
+        } else {
+            serde_json::to_vec(self).map_err(|err| Error::new(err))?
+        };
+        let temp_path = cache_path.with_extension("tmp");
+
+        let mut temp_file = File::create(&temp_path).map_err(|err| Error::new(err))?;
+        temp_file
+            .write_all(&data)
+            .and_then(|_| temp_file.sync_all())
+            .map_err(|err| Error::new(err))?;
+
+        fs::rename(&temp_path, cache_path).map_err(|err| Error::new(err))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn envelope(version: u64) -> Value {
+        json!({
+            "version": version,
+            "roots": ["/music"],
+            "scanned_at": 1700000000,
+            "songs": [{ "path": "/music/a.flac", "modified": 1234, "size": 5678 }],
+        })
+    }
+
+    #[test]
+    fn wraps_bare_song_list() {
+        let value = json!([{ "path": "/music/a.flac", "modified": 1234, "size": 5678 }]);
+        let migrated = LibraryCache::migrate_step(0, value);
+
+        assert_eq!(
+            migrated,
+            json!({
+                "version": 1,
+                "roots": [],
+                "scanned_at": 0,
+                "songs": [{ "path": "/music/a.flac", "modified": 0, "size": 0 }],
+            })
+        );
+    }
+
+    #[test]
+    fn rereads_songs_with_new_tags() {
+        for version in [1, 3, 4] {
+            let migrated = LibraryCache::migrate_step(version, envelope(version));
+
+            assert_eq!(migrated["version"], json!(version));
+            assert_eq!(migrated["roots"], json!(["/music"]));
+            assert_eq!(migrated["scanned_at"], json!(1700000000));
+            assert_eq!(migrated["songs"][0]["modified"], json!(0));
+            assert_eq!(migrated["songs"][0]["size"], json!(5678));
+        }
+    }
+
+    #[test]
+    fn keeps_songs_with_defaulted_cue_fields() {
+        assert_eq!(LibraryCache::migrate_step(2, envelope(2)), envelope(2));
+    }
+
+    #[test]
+    fn migrates_every_version_to_current() {
+        let bare = json!([{ "path": "/music/a.flac", "modified": 1234, "size": 5678 }]);
+        let migrated = LibraryCache::migrate(bare).unwrap();
+        assert_eq!(migrated["version"], json!(CACHE_VERSION));
+        assert_eq!(migrated["songs"][0]["modified"], json!(0));
+
+        for version in 1..CACHE_VERSION {
+            let migrated = LibraryCache::migrate(envelope(version)).unwrap();
+
+            assert_eq!(migrated["version"], json!(CACHE_VERSION));
+            assert_eq!(migrated["roots"], json!(["/music"]));
+            assert_eq!(migrated["songs"][0]["path"], json!("/music/a.flac"));
+            assert_eq!(migrated["songs"][0]["modified"], json!(0));
+        }
+
+        assert_eq!(
+            LibraryCache::migrate(envelope(CACHE_VERSION)).unwrap(),
+            envelope(CACHE_VERSION)
+        );
+    }
+
+    #[test]
+    fn rejects_newer_or_unversioned_caches() {
+        assert!(LibraryCache::migrate(envelope(CACHE_VERSION + 1)).is_err());
+        assert!(LibraryCache::migrate(json!({ "songs": [] })).is_err());
+    }
+}
@@ -0,0 +1,381 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Error;
+
+use crate::gain;
+
+/// A cue sheet describing how one or more audio files are split into tracks
+#[derive(Debug, Default)]
+pub struct CueSheet {
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    pub genre: Option<String>,
+    pub year: Option<u32>,
+    pub album_gain: Option<f32>,
+    pub album_peak: Option<f32>,
+    pub files: Vec<CueFile>,
+}
+
+#[derive(Debug)]
+pub struct CueFile {
+    pub path: PathBuf,
+    pub tracks: Vec<CueTrack>,
+}
+
+#[derive(Debug, Default)]
+pub struct CueTrack {
+    pub number: u32,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    pub songwriter: Option<String>,
+    /// Offsets into the file in milliseconds, the last track of a file plays until its end
+    pub start: u64,
+    pub end: Option<u64>,
+    pub gain: Option<f32>,
+    pub peak: Option<f32>,
+}
+
+impl CueSheet {
+    pub fn is_cue(path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("cue"))
+    }
+
+    pub fn read(path: &Path) -> Result<CueSheet, Error> {
+        let bytes = fs::read(path).map_err(|err| Error::new(err))?;
+
+        // Older rippers write cue sheets in Latin-1 rather than UTF-8
+        let contents = match String::from_utf8(bytes) {
+            Ok(contents) => contents,
+            Err(err) => err.into_bytes().into_iter().map(char::from).collect(),
+        };
+
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let sheet = Self::parse(contents.trim_start_matches('\u{feff}'), dir)?;
+
+        if sheet.files.iter().all(|file| file.tracks.is_empty()) {
+            return Err(Error::msg("Cue sheet does not contain any tracks"));
+        }
+
+        Ok(sheet)
+    }
+
+    fn parse(contents: &str, dir: &Path) -> Result<CueSheet, Error> {
+        let mut sheet = CueSheet::default();
+
+        for line in contents.lines() {
+            let line = line.trim();
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let track = sheet
+                .files
+                .last_mut()
+                .and_then(|file| file.tracks.last_mut());
+
+            match (command.to_uppercase().as_str(), track) {
+                ("FILE", _) => {
+                    // The file type (WAVE, MP3, ...) follows the possibly quoted name
+                    let name = match rest.rsplit_once(' ') {
+                        Some((name, _)) => Self::value(name),
+                        None => Self::value(rest),
+                    };
+                    sheet.files.push(CueFile {
+                        path: Self::resolve(dir, &name),
+                        tracks: Vec::new(),
+                    });
+                }
+                ("TRACK", _) => {
+                    let number = rest
+                        .split_whitespace()
+                        .next()
+                        .and_then(|number| number.parse().ok())
+                        .ok_or(Error::msg(format!("Invalid track line '{}'", line)))?;
+                    let file = sheet
+                        .files
+                        .last_mut()
+                        .ok_or(Error::msg("Track is not inside of a file"))?;
+                    file.tracks.push(CueTrack {
+                        number,
+                        ..Default::default()
+                    });
+                }
+                ("INDEX", Some(track)) => {
+                    let mut parts = rest.split_whitespace();
+                    if parts.next() == Some("01") {
+                        track.start = parts
+                            .next()
+                            .and_then(Self::timestamp)
+                            .ok_or(Error::msg(format!("Invalid index line '{}'", line)))?;
+                    }
+                }
+                ("TITLE", Some(track)) => track.title = Some(Self::value(rest)),
+                ("TITLE", None) => sheet.title = Some(Self::value(rest)),
+                ("PERFORMER", Some(track)) => track.performer = Some(Self::value(rest)),
+                ("PERFORMER", None) => sheet.performer = Some(Self::value(rest)),
+                ("SONGWRITER", Some(track)) => track.songwriter = Some(Self::value(rest)),
+                ("SONGWRITER", None) => sheet.songwriter = Some(Self::value(rest)),
+                ("REM", track) => match rest.split_once(' ') {
+                    Some((key, value)) if key.eq_ignore_ascii_case("GENRE") => {
+                        sheet.genre = Some(Self::value(value));
+                    }
+                    Some((key, value)) if key.eq_ignore_ascii_case("DATE") => {
+                        sheet.year = Self::value(value)
+                            .get(..4)
+                            .and_then(|year| year.parse().ok());
+                    }
+                    // Written by foobar2000 and EAC, per track and for the whole sheet
+                    Some((key, value)) => match (key.to_uppercase().as_str(), track) {
+                        ("REPLAYGAIN_TRACK_GAIN", Some(track)) => {
+                            track.gain = gain::parse_gain(&Self::value(value));
+                        }
+                        ("REPLAYGAIN_TRACK_PEAK", Some(track)) => {
+                            track.peak = gain::parse_peak(&Self::value(value));
+                        }
+                        ("REPLAYGAIN_ALBUM_GAIN", _) => {
+                            sheet.album_gain = gain::parse_gain(&Self::value(value));
+                        }
+                        ("REPLAYGAIN_ALBUM_PEAK", _) => {
+                            sheet.album_peak = gain::parse_peak(&Self::value(value));
+                        }
+                        _ => {}
+                    },
+                    None => {}
+                },
+                _ => {}
+            }
+        }
+
+        // Tracks end where the next track of the same file starts
+        for file in sheet.files.iter_mut() {
+            let starts: Vec<u64> = file.tracks.iter().map(|track| track.start).collect();
+            for (track, next_start) in file.tracks.iter_mut().zip(starts.into_iter().skip(1)) {
+                track.end = Some(next_start);
+            }
+        }
+
+        Ok(sheet)
+    }
+
+    fn value(value: &str) -> String {
+        let value = value.trim();
+        value
+            .strip_prefix('"')
+            .and_then(|value| value.strip_suffix('"'))
+            .unwrap_or(value)
+            .to_owned()
+    }
+
+    /// Converts `mm:ss:ff` (with 75 frames per second) to milliseconds
+    fn timestamp(timestamp: &str) -> Option<u64> {
+        let mut parts = timestamp.split(':').map(|part| part.parse::<u64>().ok());
+        let (Some(Some(minutes)), Some(Some(seconds)), Some(Some(frames))) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+
+        Some((minutes * 60 + seconds) * 1000 + frames * 1000 / 75)
+    }
+
+    /// Rips are often converted after the cue sheet was written, so "album.wav" may now be "album.flac"
+    fn resolve(dir: &Path, name: &str) -> PathBuf {
+        let path = dir.join(name);
+        if path.exists() {
+            return path;
+        }
+
+        let stem = path.file_stem().map(|stem| stem.to_owned());
+        dir.read_dir()
+            .ok()
+            .and_then(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .map(|entry| entry.path())
+                    .find(|sibling| {
+                        !Self::is_cue(sibling)
+                            && sibling != &path
+                            && sibling.file_stem().map(|stem| stem.to_owned()) == stem
+                    })
+            })
+            .unwrap_or(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHEET: &str = r#"REM GENRE "Progressive Rock"
+REM DATE 1973/03/01
+REM REPLAYGAIN_ALBUM_GAIN -6.50 dB
+REM REPLAYGAIN_ALBUM_PEAK 0.988525
+PERFORMER "Pink Floyd"
+TITLE "The Dark Side of the Moon"
+FILE "album.wav" WAVE
+  TRACK 01 AUDIO
+    TITLE "Speak to Me"
+    REM REPLAYGAIN_TRACK_GAIN -4.20 dB
+    REM REPLAYGAIN_TRACK_PEAK 0.5
+    INDEX 01 00:00:00
+  TRACK 02 AUDIO
+    TITLE "Breathe"
+    PERFORMER "David Gilmour"
+    INDEX 00 01:05:00
+    INDEX 01 01:07:37
+  track 03 audio
+    title Time
+    index 01 03:50:74
+"#;
+
+    /// A directory of its own per test, as they run in parallel
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("auditorium-cue-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn parses_sheet_fields() {
+        let sheet = CueSheet::parse(SHEET, Path::new("/music")).unwrap();
+
+        assert_eq!(sheet.title.as_deref(), Some("The Dark Side of the Moon"));
+        assert_eq!(sheet.performer.as_deref(), Some("Pink Floyd"));
+        assert_eq!(sheet.genre.as_deref(), Some("Progressive Rock"));
+        assert_eq!(sheet.year, Some(1973));
+        assert_eq!(sheet.album_gain, Some(-6.5));
+        assert_eq!(sheet.album_peak, Some(0.988525));
+    }
+
+    #[test]
+    fn parses_tracks() {
+        let sheet = CueSheet::parse(SHEET, Path::new("/music")).unwrap();
+
+        assert_eq!(sheet.files.len(), 1);
+        let file = &sheet.files[0];
+        assert_eq!(file.path, Path::new("/music/album.wav"));
+
+        let tracks: Vec<_> = file
+            .tracks
+            .iter()
+            .map(|track| {
+                (
+                    track.number,
+                    track.title.as_deref(),
+                    track.performer.as_deref(),
+                    track.start,
+                    track.end,
+                )
+            })
+            .collect();
+        assert_eq!(
+            tracks,
+            [
+                (1, Some("Speak to Me"), None, 0, Some(67_493)),
+                (
+                    2,
+                    Some("Breathe"),
+                    Some("David Gilmour"),
+                    67_493,
+                    Some(230_986)
+                ),
+                (3, Some("Time"), None, 230_986, None),
+            ]
+        );
+
+        assert_eq!(file.tracks[0].gain, Some(-4.2));
+        assert_eq!(file.tracks[0].peak, Some(0.5));
+        assert_eq!(file.tracks[1].gain, None);
+    }
+
+    #[test]
+    fn ends_tracks_per_file() {
+        let sheet = CueSheet::parse(
+            "FILE \"one.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n\
+             FILE \"two.flac\" WAVE\n  TRACK 02 AUDIO\n    INDEX 01 00:00:00\n\
+             \x20 TRACK 03 AUDIO\n    INDEX 01 02:00:00\n",
+            Path::new("/music"),
+        )
+        .unwrap();
+
+        assert_eq!(sheet.files.len(), 2);
+        assert_eq!(sheet.files[0].tracks[0].end, None);
+        assert_eq!(sheet.files[1].tracks[0].end, Some(120_000));
+        assert_eq!(sheet.files[1].tracks[1].start, 120_000);
+        assert_eq!(sheet.files[1].tracks[1].end, None);
+    }
+
+    #[test]
+    fn rejects_invalid_lines() {
+        assert!(CueSheet::parse("TRACK 01 AUDIO", Path::new("/music")).is_err());
+        assert!(CueSheet::parse("FILE a.wav WAVE\nTRACK one AUDIO", Path::new("/music")).is_err());
+        assert!(
+            CueSheet::parse(
+                "FILE a.wav WAVE\nTRACK 01 AUDIO\nINDEX 01 1:2",
+                Path::new("/music")
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn converts_timestamps() {
+        assert_eq!(CueSheet::timestamp("00:00:00"), Some(0));
+        assert_eq!(CueSheet::timestamp("01:02:75"), Some(63_000));
+        assert_eq!(CueSheet::timestamp("74:59:74"), Some(4_499_986));
+        assert_eq!(CueSheet::timestamp("01:02"), None);
+        assert_eq!(CueSheet::timestamp("aa:02:03"), None);
+    }
+
+    #[test]
+    fn reads_latin1_sheets() {
+        let dir = test_dir("latin1");
+        let path = dir.join("album.cue");
+        let mut contents = b"PERFORMER \"Bj".to_vec();
+        contents.push(0xF6);
+        contents
+            .extend(b"rk\"\nFILE \"album.flac\" WAVE\n  TRACK 01 AUDIO\n    INDEX 01 00:00:00\n");
+        fs::write(&path, contents).unwrap();
+
+        let sheet = CueSheet::read(&path).unwrap();
+        assert_eq!(sheet.performer.as_deref(), Some("Björk"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_sheets_without_tracks() {
+        let dir = test_dir("empty");
+        let path = dir.join("album.cue");
+        fs::write(
+            &path,
+            "\u{feff}TITLE \"Nothing\"\nFILE \"album.flac\" WAVE\n",
+        )
+        .unwrap();
+
+        assert!(CueSheet::read(&path).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resolves_converted_files() {
+        let dir = test_dir("resolve");
+        fs::write(dir.join("album.cue"), "").unwrap();
+        fs::write(dir.join("album.flac"), "").unwrap();
+        fs::write(dir.join("other.wav"), "").unwrap();
+
+        assert_eq!(CueSheet::resolve(&dir, "album.wav"), dir.join("album.flac"));
+        assert_eq!(CueSheet::resolve(&dir, "other.wav"), dir.join("other.wav"));
+        assert_eq!(
+            CueSheet::resolve(&dir, "missing.wav"),
+            dir.join("missing.wav")
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
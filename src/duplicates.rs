@@ -0,0 +1,231 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+use crate::{songs::Song, utilities};
+
+/// Copies whose lengths differ by at most this many seconds can still be the same recording
+const DURATION_TOLERANCE: u64 = 2;
+
+/// Files are hashed through a buffer of this many bytes rather than read whole
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Content hashes of whole files, kept for as long as the modification time and size of the file
+/// stay the same. Files that could not be read are kept without a hash, so they are not retried
+#[derive(Default)]
+pub struct ContentHashes {
+    hashes: HashMap<PathBuf, ((u64, u64), Option<u64>)>,
+}
+
+/// A file to hash, with the modification time and size it had when it was read into the library
+pub struct HashedFile {
+    path: PathBuf,
+    fingerprint: (u64, u64),
+    hash: Option<u64>,
+}
+
+impl ContentHashes {
+    fn get(&self, song: &Song) -> Option<u64> {
+        self.hashes
+            .get(&song.path)
+            .filter(|(fingerprint, _)| *fingerprint == (song.modified, song.size))
+            .and_then(|(_, hash)| *hash)
+    }
+
+    /// Whole files in `songs` that were not hashed since they last changed. Tracks of a cue sheet
+    /// share their file, so only whole files are compared by content
+    pub fn missing(&self, songs: &[Song]) -> Vec<HashedFile> {
+        songs
+            .iter()
+            .filter(|song| song.cue.is_none())
+            .filter(|song| {
+                self.hashes
+                    .get(&song.path)
+                    .is_none_or(|(fingerprint, _)| *fingerprint != (song.modified, song.size))
+            })
+            .map(|song| HashedFile {
+                path: song.path.clone(),
+                fingerprint: (song.modified, song.size),
+                hash: None,
+            })
+            .collect()
+    }
+
+    pub fn insert(&mut self, hashed: Vec<HashedFile>) {
+        self.hashes.extend(
+            hashed
+                .into_iter()
+                .map(|file| (file.path, (file.fingerprint, file.hash))),
+        );
+    }
+}
+
+/// Reads every file, which takes a while for a whole library, so this is done away from the app
+pub fn hash_files(files: Vec<HashedFile>) -> Vec<HashedFile> {
+    files
+        .into_par_iter()
+        .map(|file| HashedFile {
+            hash: hash_file(&file.path),
+            ..file
+        })
+        .collect()
+}
+
+/// Groups likely copies of the same song by their artist, title, and length, and optionally by
+/// identical file contents as far as they were hashed. Each group lists indices into `songs`, best
+/// copy first
+pub fn find(songs: &[Song], hashes: Option<&ContentHashes>) -> Vec<Vec<usize>> {
+    let mut sets = DisjointSets::new(songs.len());
+
+    let mut by_name: HashMap<(String, String), Vec<usize>> = HashMap::new();
+    for (i, song) in songs.iter().enumerate() {
+        let artist = song.artists.first().unwrap_or(&song.artist);
+        by_name
+            .entry((normalize(artist), normalize(&song.title)))
+            .or_default()
+            .push(i);
+    }
+
+    for mut named in by_name.into_values().filter(|named| named.len() > 1) {
+        named.sort_by_key(|i| songs[*i].duration);
+
+        // Compared against the shortest song of the group rather than the previous one, so that
+        // lengths a little apart each do not chain into one long group
+        let mut shortest = named[0];
+        for &i in &named[1..] {
+            let close = match (songs[shortest].duration, songs[i].duration) {
+                (Some(first), Some(second)) => second - first <= DURATION_TOLERANCE,
+                (None, None) => true,
+                _ => false,
+            };
+
+            if close {
+                sets.union(shortest, i);
+            } else {
+                shortest = i;
+            }
+        }
+    }
+
+    // Tracks of a cue sheet share their file, so only whole files are compared by content
+    if let Some(hashes) = hashes {
+        let mut by_hash: HashMap<u64, usize> = HashMap::new();
+        let hashed = songs
+            .iter()
+            .enumerate()
+            .filter(|(_, song)| song.cue.is_none())
+            .filter_map(|(i, song)| hashes.get(song).map(|hash| (i, hash)));
+
+        for (i, hash) in hashed {
+            match by_hash.get(&hash) {
+                Some(first) => sets.union(*first, i),
+                None => {
+                    by_hash.insert(hash, i);
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..songs.len() {
+        groups.entry(sets.find(i)).or_default().push(i);
+    }
+
+    let mut groups: Vec<Vec<usize>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .map(|mut group| {
+            group.sort_by(|first, second| {
+                compare_quality(&songs[*second], &songs[*first])
+                    .then_with(|| songs[*first].path.cmp(&songs[*second].path))
+            });
+            group
+        })
+        .collect();
+
+    groups.sort_by(|first, second| {
+        utilities::cmp_ignore_case(&songs[first[0]].title, &songs[second[0]].title)
+    });
+    groups
+}
+
+/// Lowercase letters and digits only, so "Don't Stop" matches "Dont Stop " and "don’t stop"
+fn normalize(text: &str) -> String {
+    text.chars()
+        .filter(|char| char.is_alphanumeric())
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+/// Lossless copies are preferred, then higher bitrates and sample rates
+fn compare_quality(first: &Song, second: &Song) -> Ordering {
+    is_lossless(first)
+        .cmp(&is_lossless(second))
+        .then(first.bitrate.cmp(&second.bitrate))
+        .then(first.sample_rate.cmp(&second.sample_rate))
+}
+
+fn is_lossless(song: &Song) -> bool {
+    song.codec.as_deref().is_some_and(|codec| {
+        matches!(
+            codec.to_uppercase().as_str(),
+            "FLAC" | "ALAC" | "WAV" | "AIFF" | "APE" | "WAVPACK" | "PCM_S16LE" | "PCM_S24LE"
+        )
+    })
+}
+
+fn hash_file(path: &Path) -> Option<u64> {
+    let mut file = File::open(path).ok()?;
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+    let mut hash = utilities::hash_stable(&[]);
+
+    loop {
+        match file.read(&mut buffer).ok()? {
+            0 => return Some(hash),
+            read => hash = utilities::hash_stable_from(hash, &buffer[..read]),
+        }
+    }
+}
+
+/// Union-find over song indices, used to merge groups found by name and by content
+struct DisjointSets {
+    parents: Vec<usize>,
+}
+
+impl DisjointSets {
+    fn new(len: usize) -> DisjointSets {
+        DisjointSets {
+            parents: (0..len).collect(),
+        }
+    }
+
+    fn find(&mut self, i: usize) -> usize {
+        let mut root = i;
+        while self.parents[root] != root {
+            root = self.parents[root];
+        }
+
+        // Point everything on the way straight at the root for faster lookups next time
+        let mut i = i;
+        while self.parents[i] != root {
+            let parent = self.parents[i];
+            self.parents[i] = root;
+            i = parent;
+        }
+
+        root
+    }
+
+    fn union(&mut self, first: usize, second: usize) {
+        let (first, second) = (self.find(first), self.find(second));
+        if first != second {
+            self.parents[second] = first;
+        }
+    }
+}
@@ -1,21 +1,57 @@
 use std::{
+    collections::HashMap,
     fs::{self, File},
     io::BufReader,
     path::{Path, PathBuf},
 };
 
 use color_eyre::eyre::Error;
+use glob::Pattern;
 use ratatui::style::Color;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::{gain::ReplayGainMode, infer::PathTemplate, playlist::ExportPaths, songs::SortOrder};
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Config {
     pub color_border: Color,
     pub color_headers: Color,
     pub color_row: Color,
     #[serde(default = "default_opt_cover")]
     pub show_cover: bool,
+    #[serde(default)]
+    pub watch_library: bool,
+    #[serde(default = "default_opt_watch_debounce")]
+    pub watch_debounce_ms: u64,
+    #[serde(default = "default_opt_extensions")]
+    pub extensions: Vec<String>,
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    #[serde(default)]
+    pub skip_hidden: bool,
+    #[serde(default)]
+    pub binary_cache: bool,
+    #[serde(default = "default_opt_path_templates")]
+    pub path_templates: Vec<String>,
+    #[serde(default = "default_opt_artist_separators")]
+    pub artist_separators: Vec<String>,
+    #[serde(default)]
+    pub artist_aliases: HashMap<String, String>,
+    #[serde(default)]
+    pub sort_order: SortOrder,
+    #[serde(default)]
+    pub export_paths: ExportPaths,
+    #[serde(default)]
+    pub replay_gain: ReplayGainMode,
+    #[serde(default)]
+    pub duplicates_content_hash: bool,
     music_directory: PathBuf,
+    #[serde(default)]
+    library_roots: Vec<PathBuf>,
+    #[serde(skip)]
+    exclude_patterns: Vec<Pattern>,
     #[serde(skip)]
     manual_music_directory: Option<PathBuf>,
 }
@@ -24,6 +60,34 @@ const fn default_opt_cover() -> bool {
     cfg!(feature = "art")
 }
 
+const fn default_opt_watch_debounce() -> u64 {
+    2000
+}
+
+fn default_opt_extensions() -> Vec<String> {
+    [
+        "mp3", "flac", "ogg", "oga", "opus", "m4a", "aac", "wav", "aiff", "aif", "wma", "ape",
+        "wv", "mka", "mpc",
+    ]
+    .into_iter()
+    .map(|extension| extension.to_owned())
+    .collect()
+}
+
+fn default_opt_path_templates() -> Vec<String> {
+    ["{artist}/{album}/{track} - {title}", "{artist} - {title}"]
+        .into_iter()
+        .map(|template| template.to_owned())
+        .collect()
+}
+
+fn default_opt_artist_separators() -> Vec<String> {
+    [";", " feat. ", " feat ", " ft. ", " featuring "]
+        .into_iter()
+        .map(|separator| separator.to_owned())
+        .collect()
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -31,7 +95,23 @@ impl Default for Config {
             color_headers: Color::Green,
             color_row: Color::Indexed(246),
             music_directory: music_path().unwrap_or(Path::new("Music").to_path_buf()),
+            library_roots: Vec::new(),
             show_cover: cfg!(feature = "art"),
+            watch_library: false,
+            watch_debounce_ms: default_opt_watch_debounce(),
+            extensions: default_opt_extensions(),
+            exclude: Vec::new(),
+            max_depth: None,
+            skip_hidden: false,
+            binary_cache: false,
+            path_templates: default_opt_path_templates(),
+            artist_separators: default_opt_artist_separators(),
+            artist_aliases: HashMap::new(),
+            sort_order: SortOrder::default(),
+            export_paths: ExportPaths::default(),
+            replay_gain: ReplayGainMode::default(),
+            duplicates_content_hash: false,
+            exclude_patterns: Vec::new(),
             manual_music_directory: None,
         }
     }
@@ -49,7 +129,21 @@ impl Config {
         let path = config_path()?;
 
         if let Ok(file) = File::open(&path) {
-            serde_json::from_reader(BufReader::new(file)).map_err(|err| Error::new(err))
+            let mut config: Config =
+                serde_json::from_reader(BufReader::new(file)).map_err(|err| Error::new(err))?;
+            config.exclude_patterns = config
+                .exclude
+                .iter()
+                .map(|exclude| {
+                    Pattern::new(exclude.trim_end_matches('/')).map_err(|err| {
+                        Error::msg(format!("Invalid exclude pattern '{}': {}", exclude, err))
+                    })
+                })
+                .collect::<Result<Vec<Pattern>, Error>>()?;
+            for template in &config.path_templates {
+                PathTemplate::parse(template)?;
+            }
+            Ok(config)
         } else {
             let defaults = Config::default();
             let json = serde_json::to_string_pretty(&defaults).map_err(|err| Error::new(err))?;
@@ -71,12 +165,24 @@ impl Config {
             .unwrap_or(false)
     }
 
-    pub fn music_directory(&self) -> &PathBuf {
+    /// The music directory followed by any additional library roots, or only the `--dir` override
+    pub fn music_directories(&self) -> Vec<PathBuf> {
         if let Some(dir) = &self.manual_music_directory {
-            &dir
-        } else {
-            &self.music_directory
+            return vec![dir.clone()];
+        }
+
+        let mut directories = vec![self.music_directory.clone()];
+        for root in &self.library_roots {
+            if !directories.contains(root) {
+                directories.push(root.clone());
+            }
         }
+
+        directories
+    }
+
+    pub fn exclude_patterns(&self) -> &[Pattern] {
+        &self.exclude_patterns
     }
 
     pub fn reload(&mut self) -> Result<(), Error> {
@@ -85,29 +191,47 @@ impl Config {
     }
 }
 
-pub fn cache_path() -> Result<PathBuf, Error> {
+pub fn cache_path(binary: bool) -> Result<PathBuf, Error> {
     let root_dir = dirs::cache_dir().ok_or(Error::msg("Could not load cache directory!"))?;
     let cache_dir = root_dir.join("auditorium");
     fs::create_dir_all(&cache_dir).map_err(|err| Error::new(err))?;
-    Ok(cache_dir.join("cache.json"))
+    Ok(cache_dir.join(if binary { "cache.bin" } else { "cache.json" }))
 }
 
-pub fn art_path() -> Result<PathBuf, Error> {
+pub fn scan_log_path() -> Result<PathBuf, Error> {
     let root_dir = dirs::cache_dir().ok_or(Error::msg("Could not load cache directory!"))?;
     let cache_dir = root_dir.join("auditorium");
-    let picture_dir = cache_dir.join("pictures");
-    fs::create_dir_all(&picture_dir).map_err(|err| Error::new(err))?;
-    Ok(picture_dir)
+    fs::create_dir_all(&cache_dir).map_err(|err| Error::new(err))?;
+    Ok(cache_dir.join("scan.log"))
+}
+
+pub fn hidden_path() -> Result<PathBuf, Error> {
+    let root_dir = dirs::data_dir().ok_or(Error::msg("Could not load data directory!"))?;
+    let data_dir = root_dir.join("auditorium");
+    fs::create_dir_all(&data_dir).map_err(|err| Error::new(err))?;
+    Ok(data_dir.join("hidden.json"))
 }
 
-pub fn art_path_delete() -> Result<(), Error> {
+pub fn exports_path() -> Result<PathBuf, Error> {
+    let root_dir = dirs::data_dir().ok_or(Error::msg("Could not load data directory!"))?;
+    let exports_dir = root_dir.join("auditorium").join("exports");
+    fs::create_dir_all(&exports_dir).map_err(|err| Error::new(err))?;
+    Ok(exports_dir)
+}
+
+pub fn playlists_path() -> Result<PathBuf, Error> {
+    let root_dir = dirs::data_dir().ok_or(Error::msg("Could not load data directory!"))?;
+    let playlists_dir = root_dir.join("auditorium").join("playlists");
+    fs::create_dir_all(&playlists_dir).map_err(|err| Error::new(err))?;
+    Ok(playlists_dir)
+}
+
+pub fn art_path() -> Result<PathBuf, Error> {
     let root_dir = dirs::cache_dir().ok_or(Error::msg("Could not load cache directory!"))?;
     let cache_dir = root_dir.join("auditorium");
     let picture_dir = cache_dir.join("pictures");
-    if fs::exists(&picture_dir).is_ok() {
-        let _ = fs::remove_dir_all(picture_dir);
-    }
-    Ok(())
+    fs::create_dir_all(&picture_dir).map_err(|err| Error::new(err))?;
+    Ok(picture_dir)
 }
 
 fn music_path() -> Result<PathBuf, Error> {
@@ -0,0 +1,152 @@
+use std::{path::Path, process::Command};
+
+use color_eyre::eyre::Error;
+use serde::{Deserialize, Serialize};
+
+/// ReplayGain 2.0 normalizes to -18 LUFS
+const REFERENCE_LOUDNESS: f32 = -18.0;
+
+/// Which gain mpv applies, set in the config
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ReplayGainMode {
+    #[default]
+    Off,
+    Track,
+    Album,
+}
+
+/// Loudness of a song as measured by ffmpeg
+pub struct Loudness {
+    /// Integrated loudness in LUFS
+    pub integrated: f32,
+    /// True peak as a linear amplitude, 1.0 being full scale
+    pub peak: f32,
+}
+
+impl ReplayGainMode {
+    pub fn mpv_value(self) -> &'static str {
+        match self {
+            ReplayGainMode::Off => "no",
+            ReplayGainMode::Track => "track",
+            ReplayGainMode::Album => "album",
+        }
+    }
+}
+
+/// Parses gain tags like "-6.48 dB"
+pub fn parse_gain(value: &str) -> Option<f32> {
+    value
+        .split_whitespace()
+        .next()
+        .and_then(|gain| gain.parse().ok())
+        .filter(|gain: &f32| gain.is_finite())
+}
+
+pub fn parse_peak(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .parse()
+        .ok()
+        .filter(|peak: &f32| peak.is_finite() && *peak >= 0.0)
+}
+
+/// Opus R128 gains are Q7.8 fixed point numbers relative to -23 LUFS, 5 dB quieter than ReplayGain
+pub fn parse_r128_gain(value: &str) -> Option<f32> {
+    value
+        .trim()
+        .parse::<i16>()
+        .ok()
+        .map(|gain| gain as f32 / 256.0 + 5.0)
+}
+
+pub fn gain_from_loudness(loudness: f32) -> f32 {
+    REFERENCE_LOUDNESS - loudness
+}
+
+pub fn loudness_from_gain(gain: f32) -> f32 {
+    REFERENCE_LOUDNESS - gain
+}
+
+/// Lowers the gain where needed so that the peak does not clip
+pub fn clip_safe(gain: f32, peak: Option<f32>) -> f32 {
+    match peak.filter(|peak| *peak > 0.0) {
+        Some(peak) => gain.min(-20.0 * peak.log10()),
+        None => gain,
+    }
+}
+
+/// Measures a song (or the part of it from `start` to `end` in milliseconds) with ffmpeg's EBU R128
+/// filter. See: https://ffmpeg.org/ffmpeg-filters.html#ebur128-1
+pub fn analyze(path: &Path, start: Option<u64>, end: Option<u64>) -> Result<Loudness, Error> {
+    let mut command = Command::new("ffmpeg");
+    command.arg("-hide_banner").arg("-nostats");
+
+    if let Some(start) = start {
+        command.arg("-ss").arg(format!("{}", start as f64 / 1000.0));
+        if let Some(end) = end {
+            command
+                .arg("-t")
+                .arg(format!("{}", end.saturating_sub(start) as f64 / 1000.0));
+        }
+    }
+
+    let output = command
+        .arg("-i")
+        .arg(path)
+        .args([
+            "-map",
+            "0:a:0",
+            "-af",
+            "ebur128=peak=true",
+            "-f",
+            "null",
+            "-",
+        ])
+        .output()
+        .map_err(|err| Error::msg(format!("Could not run ffmpeg: {}", err)))?;
+
+    if !output.status.success() {
+        return Err(Error::msg(format!(
+            "ffmpeg could not analyze the file ({})",
+            output.status
+        )));
+    }
+
+    // The summary is printed last, after the per-frame log
+    let log = String::from_utf8_lossy(&output.stderr);
+    let summary = log.rsplit_once("Summary:").map(|(_, summary)| summary);
+    let value = |label: &str| {
+        summary?
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix(label))
+            .find_map(|value| value.split_whitespace().next()?.parse::<f32>().ok())
+    };
+
+    match (value("I:"), value("Peak:")) {
+        (Some(integrated), Some(peak)) if integrated.is_finite() => Ok(Loudness {
+            integrated,
+            peak: 10f32.powf(peak / 20.0),
+        }),
+        _ => Err(Error::msg("ffmpeg did not report the loudness of the file")),
+    }
+}
+
+/// Combines the loudness of the tracks of an album, weighted by their length. This approximates
+/// measuring the album as a whole without decoding it again
+pub fn album_loudness(tracks: &[(f32, u64)]) -> Option<f32> {
+    if tracks.is_empty() {
+        return None;
+    }
+
+    let (energy, total) =
+        tracks
+            .iter()
+            .fold((0.0, 0.0), |(energy, total), (loudness, duration)| {
+                let duration = (*duration).max(1) as f64;
+                (
+                    energy + 10f64.powf(*loudness as f64 / 10.0) * duration,
+                    total + duration,
+                )
+            });
+    Some((10.0 * (energy / total).log10()) as f32)
+}
@@ -0,0 +1,382 @@
+use std::path::{Component, Path};
+
+use color_eyre::eyre::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::{files::Config, songs::Song};
+
+/// One flag per field that can be missing from the tags of a song
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct TagFields {
+    pub title: bool,
+    pub artist: bool,
+    pub album: bool,
+    pub album_artist: bool,
+    pub track: bool,
+    pub disc: bool,
+    pub year: bool,
+    pub genres: bool,
+}
+
+/// Fills fields missing from the tags with values taken from the path of the song, using the first
+/// configured template that matches it, like `{artist}/{album}/{track} - {title}`
+pub struct PathTemplates {
+    templates: Vec<PathTemplate>,
+}
+
+/// A template split into its folder and file name components, matched against the end of a path
+pub struct PathTemplate {
+    components: Vec<Vec<Segment>>,
+}
+
+enum Segment {
+    Text(String),
+    Field(Field),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    AlbumArtist,
+    Track,
+    Disc,
+    Year,
+    Genre,
+    /// Any other name, such as `{_}`, matches text that is not used
+    Ignored,
+}
+
+impl TagFields {
+    /// Fields that were not found in the tags read from the file or cue sheet
+    pub fn missing(song: &Song) -> TagFields {
+        TagFields {
+            title: song.title.is_empty(),
+            artist: song.artist.is_empty(),
+            album: song.album.is_empty(),
+            album_artist: song.album_artist.is_none(),
+            track: song.track.is_empty(),
+            disc: song.disc.is_none(),
+            year: song.year.is_none(),
+            genres: song.genres.is_empty(),
+        }
+    }
+
+    pub fn any(&self) -> bool {
+        *self != TagFields::default()
+    }
+}
+
+impl PathTemplates {
+    pub fn new(config: &Config) -> PathTemplates {
+        PathTemplates {
+            // Invalid templates are already reported when the config is loaded
+            templates: config
+                .path_templates
+                .iter()
+                .filter_map(|template| PathTemplate::parse(template).ok())
+                .collect(),
+        }
+    }
+
+    /// Replaces whatever was filled in before, since the templates may have changed since the song was
+    /// cached. Fields no template provides get placeholders such as "Unknown"
+    pub fn apply(&self, song: &mut Song) {
+        let missing = song.missing;
+        if missing.title {
+            song.title.clear();
+        }
+        if missing.artist {
+            song.artist.clear();
+        }
+        if missing.album {
+            song.album.clear();
+        }
+        if missing.album_artist {
+            song.album_artist = None;
+        }
+        if missing.track {
+            song.track.clear();
+        }
+        if missing.disc {
+            song.disc = None;
+        }
+        if missing.year {
+            song.year = None;
+        }
+        if missing.genres {
+            song.genres.clear();
+        }
+        song.inferred = TagFields::default();
+
+        let relative = song.path.strip_prefix(&song.root).unwrap_or(&song.path);
+        if missing.any()
+            && let Some(values) = self
+                .templates
+                .iter()
+                .find_map(|template| template.matches(relative))
+        {
+            for (field, value) in values {
+                Self::fill(song, field, value);
+            }
+        }
+
+        if song.title.is_empty() {
+            song.title = "Unknown".to_owned();
+        }
+        if song.artist.is_empty() {
+            song.artist = "Unknown".to_owned();
+        }
+        if song.album.is_empty() {
+            song.album = "Single".to_owned();
+        }
+        if song.track.is_empty() {
+            song.track = "1".to_owned();
+        }
+        if song.genres.is_empty() {
+            song.genres = vec!["Unknown".to_owned()];
+        }
+    }
+
+    fn fill(song: &mut Song, field: Field, value: String) {
+        let (missing, inferred) = (&song.missing, &mut song.inferred);
+        match field {
+            Field::Title if missing.title => {
+                song.title = value;
+                inferred.title = true;
+            }
+            Field::Artist if missing.artist => {
+                song.artist = value;
+                inferred.artist = true;
+            }
+            Field::Album if missing.album => {
+                song.album = value;
+                inferred.album = true;
+            }
+            Field::AlbumArtist if missing.album_artist => {
+                song.album_artist = Some(value);
+                inferred.album_artist = true;
+            }
+            Field::Track if missing.track => {
+                song.track = value;
+                inferred.track = true;
+            }
+            Field::Disc if missing.disc => {
+                song.disc = value.parse().ok();
+                inferred.disc = true;
+            }
+            Field::Year if missing.year => {
+                song.year = value.parse().ok();
+                inferred.year = true;
+            }
+            Field::Genre if missing.genres => {
+                song.genres = vec![value];
+                inferred.genres = true;
+            }
+            _ => {}
+        }
+    }
+}
+
+impl PathTemplate {
+    pub fn parse(template: &str) -> Result<PathTemplate, Error> {
+        let invalid =
+            |reason: &str| Error::msg(format!("Invalid path template '{}': {}", template, reason));
+
+        let components = template
+            .trim_matches('/')
+            .split('/')
+            .map(|component| {
+                let mut segments = Vec::new();
+                let mut rest = component;
+
+                while let Some(start) = rest.find('{') {
+                    if start > 0 {
+                        segments.push(Segment::Text(rest[..start].to_owned()));
+                    }
+
+                    let end = rest[start..]
+                        .find('}')
+                        .ok_or_else(|| invalid("a '{' is never closed"))?;
+                    let field = Field::from_name(&rest[start + 1..start + end]);
+
+                    // Two fields in a row cannot be told apart
+                    if let Some(Segment::Field(_)) = segments.last() {
+                        return Err(invalid("fields must be separated by some text"));
+                    }
+
+                    segments.push(Segment::Field(field));
+                    rest = &rest[start + end + 1..];
+                }
+
+                if !rest.is_empty() {
+                    segments.push(Segment::Text(rest.to_owned()));
+                }
+
+                if segments.is_empty() {
+                    Err(invalid("folders cannot be empty"))
+                } else {
+                    Ok(segments)
+                }
+            })
+            .collect::<Result<Vec<Vec<Segment>>, Error>>()?;
+
+        Ok(PathTemplate { components })
+    }
+
+    /// Matches the last folders and file name (without its extension) of the path. Numbers must be
+    /// numbers, so `{track} - {title}` does not match "Artist - Title"
+    fn matches(&self, path: &Path) -> Option<Vec<(Field, String)>> {
+        let mut names: Vec<String> = path
+            .components()
+            .filter_map(|component| match component {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+
+        if let Some(stem) = path.file_stem()
+            && let Some(last) = names.last_mut()
+        {
+            *last = stem.to_string_lossy().into_owned();
+        }
+
+        let names = names.get(names.len().checked_sub(self.components.len())?..)?;
+
+        let mut values = Vec::new();
+        for (segments, name) in self.components.iter().zip(names) {
+            values.extend(Self::match_component(segments, name)?);
+        }
+
+        Some(values)
+    }
+
+    fn match_component(segments: &[Segment], name: &str) -> Option<Vec<(Field, String)>> {
+        match segments.split_first() {
+            None => name.is_empty().then(Vec::new),
+            Some((Segment::Text(text), rest)) => {
+                Self::match_component(rest, name.strip_prefix(text.as_str())?)
+            }
+            Some((Segment::Field(field), rest)) => {
+                // The shortest value that lets the rest match, so "{track} - {title}" splits at the
+                // first " - "
+                let ends: Vec<usize> = match rest.first() {
+                    None => vec![name.len()],
+                    Some(_) => name
+                        .char_indices()
+                        .map(|(i, _)| i)
+                        .skip(1)
+                        .chain([name.len()])
+                        .collect(),
+                };
+
+                ends.into_iter().find_map(|end| {
+                    let value = name[..end].trim();
+                    let value = field.normalize(value)?;
+                    let mut values = Self::match_component(rest, &name[end..])?;
+                    values.insert(0, (*field, value));
+                    Some(values)
+                })
+            }
+        }
+    }
+}
+
+impl Field {
+    fn from_name(name: &str) -> Field {
+        match name.trim().to_lowercase().as_str() {
+            "title" => Field::Title,
+            "artist" => Field::Artist,
+            "album" => Field::Album,
+            "album_artist" | "albumartist" => Field::AlbumArtist,
+            "track" => Field::Track,
+            "disc" => Field::Disc,
+            "year" => Field::Year,
+            "genre" => Field::Genre,
+            _ => Field::Ignored,
+        }
+    }
+
+    /// Leading zeros are dropped from numbers, like "01" for the first track
+    fn normalize(self, value: &str) -> Option<String> {
+        if value.is_empty() {
+            return None;
+        }
+
+        match self {
+            Field::Track | Field::Disc | Field::Year => {
+                value.parse::<u32>().ok().map(|number| number.to_string())
+            }
+            _ => Some(value.to_owned()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(template: &str, path: &str) -> Option<Vec<(Field, String)>> {
+        PathTemplate::parse(template)
+            .unwrap()
+            .matches(Path::new(path))
+    }
+
+    #[test]
+    fn matches_nested_templates() {
+        assert_eq!(
+            matches(
+                "{artist}/{album} ({year})/{disc}-{track} - {title}",
+                "/music/Nirvana/Nevermind (1991)/1-01 - Smells Like Teen Spirit.flac"
+            ),
+            Some(vec![
+                (Field::Artist, "Nirvana".to_owned()),
+                (Field::Album, "Nevermind".to_owned()),
+                (Field::Year, "1991".to_owned()),
+                (Field::Disc, "1".to_owned()),
+                (Field::Track, "1".to_owned()),
+                (Field::Title, "Smells Like Teen Spirit".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn matches_literal_separators() {
+        assert_eq!(
+            matches("{track} - {title}", "Album/03 - Come As You Are - Live.mp3"),
+            Some(vec![
+                (Field::Track, "3".to_owned()),
+                (Field::Title, "Come As You Are - Live".to_owned()),
+            ])
+        );
+        assert_eq!(
+            matches("[{genre}] {artist}_{_}", "[Grunge] Nirvana_bonus.ogg"),
+            Some(vec![
+                (Field::Genre, "Grunge".to_owned()),
+                (Field::Artist, "Nirvana".to_owned()),
+                (Field::Ignored, "bonus".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn rejects_paths_that_do_not_match() {
+        // Numbers must be numbers
+        assert_eq!(matches("{track} - {title}", "Artist - Title.mp3"), None);
+        // Too few folders
+        assert_eq!(matches("{artist}/{album}/{title}", "Album/Title.mp3"), None);
+        // Missing literal text
+        assert_eq!(matches("{artist} - {title}", "Nirvana_Lithium.mp3"), None);
+        assert_eq!(matches("[{genre}] {title}", "Grunge Lithium.mp3"), None);
+        // Empty values
+        assert_eq!(matches("{artist} - {title}", " - Lithium.mp3"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_templates() {
+        assert!(PathTemplate::parse("{artist}{title}").is_err());
+        assert!(PathTemplate::parse("{artist/{title}").is_err());
+        assert!(PathTemplate::parse("{artist}//{title}").is_err());
+    }
+}
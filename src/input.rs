@@ -9,6 +9,7 @@ use ratatui::layout::Position;
 use crate::app::App;
 use crate::app::Message;
 use crate::app::NavState;
+use crate::playlist::ExportFormat;
 
 pub fn handle_input(app: &mut App) -> Message {
     let event;
@@ -44,6 +45,36 @@ pub fn handle_input(app: &mut App) -> Message {
         ..
     }) = event
     {
+        if app.prompt.is_some() {
+            return match code {
+                KeyCode::Esc => Message::Escape,
+                KeyCode::Enter => Message::SubmitPrompt,
+                KeyCode::Backspace => Message::ModifyPrompt(None),
+                KeyCode::Char(c) => Message::ModifyPrompt(Some(c)),
+                _ => Message::None,
+            };
+        }
+
+        if app.confirm.is_some() {
+            return match code {
+                KeyCode::Char('y') | KeyCode::Enter => Message::SubmitConfirm,
+                KeyCode::Char('n') | KeyCode::Esc => Message::Escape,
+                _ => Message::None,
+            };
+        }
+
+        if app.tag_editor.is_some() {
+            return match code {
+                KeyCode::Esc => Message::Escape,
+                KeyCode::Enter => Message::SaveTags,
+                KeyCode::Tab | KeyCode::Down => Message::CycleTagField(true),
+                KeyCode::BackTab | KeyCode::Up => Message::CycleTagField(false),
+                KeyCode::Backspace => Message::ModifyTag(None),
+                KeyCode::Char(c) => Message::ModifyTag(Some(c)),
+                _ => Message::None,
+            };
+        }
+
         if app.song_query.is_some() && app.nav_state == NavState::Search {
             if code == KeyCode::Esc {
                 return Message::Escape;
@@ -72,6 +103,45 @@ pub fn handle_input(app: &mut App) -> Message {
             KeyCode::Right => return Message::SongSeek(5),
             KeyCode::Left => return Message::SongSeek(-5),
             KeyCode::Char('a') => return Message::PlayAll,
+            KeyCode::Char('e') => return Message::ShowReport,
+            KeyCode::Char('D') => return Message::ShowDuplicates,
+            KeyCode::Char('l') => return Message::ShowLyrics,
+            KeyCode::Char('f') => {
+                if let NavState::Lyrics(_) = app.nav_state {
+                    return Message::FollowLyrics;
+                }
+            }
+            KeyCode::Char('h') => {
+                if let NavState::Duplicates(table_state) = &app.nav_state
+                    && let Some(selected) = table_state.selected()
+                {
+                    return Message::ToggleHidden(selected);
+                }
+            }
+            KeyCode::Char('H') => {
+                if let NavState::Duplicates(_) = app.nav_state {
+                    return Message::HideLesserCopies;
+                }
+            }
+            KeyCode::Char('o') => return Message::CycleSortOrder,
+            KeyCode::Char('t') => return Message::EditTags,
+            KeyCode::Char('s') => return Message::AddToPlaylist,
+            KeyCode::Char('N') => return Message::NewPlaylist,
+            KeyCode::Char('w') => return Message::Export(ExportFormat::M3u8),
+            KeyCode::Char('W') => return Message::Export(ExportFormat::Xspf),
+            KeyCode::Char('S') => return Message::NewSmartPlaylist,
+            KeyCode::Char('E') | KeyCode::Char('x') | KeyCode::Char('O') | KeyCode::Char('L') => {
+                if let NavState::Playlists(table_state) = &app.nav_state
+                    && let Some(selected) = table_state.selected()
+                {
+                    return match code {
+                        KeyCode::Char('E') => Message::RenamePlaylist(selected),
+                        KeyCode::Char('O') => Message::CycleSmartOrder(selected),
+                        KeyCode::Char('L') => Message::CycleSmartLimit(selected),
+                        _ => Message::ReplaceUpNext(selected),
+                    };
+                }
+            }
             KeyCode::BackTab => return Message::NavStatePrev,
             KeyCode::Tab => return Message::NavStateNext,
             KeyCode::Esc => return Message::Escape,
@@ -87,12 +157,22 @@ pub fn handle_input(app: &mut App) -> Message {
                     if let Some(selected) = table_state.selected() {
                         return Message::DeleteNextUp(selected + 1);
                     }
+                } else if let NavState::Playlists(table_state) = &app.nav_state
+                    && let Some(selected) = table_state.selected()
+                {
+                    return Message::DeletePlaylist(selected);
                 }
             }
             KeyCode::Char('j') | KeyCode::PageDown | KeyCode::Down => {
                 let elements = match app.nav_state {
                     NavState::UpNext(_) => app.songs.songs_in_next_up(),
                     NavState::Library(_) => app.songs.songs_in_library(),
+                    NavState::Playlists(_) => app.playlists.len(),
+                    NavState::Report(_) => app.songs.report.failures.len(),
+                    NavState::Duplicates(_) => app.duplicates.iter().flatten().count(),
+                    NavState::Lyrics(_) => {
+                        app.lyrics.as_ref().map_or(0, |lyrics| lyrics.lines.len())
+                    }
                     _ => 0,
                 };
                 return Message::NavStateInnerNext(code != KeyCode::PageDown, elements);
@@ -101,6 +181,12 @@ pub fn handle_input(app: &mut App) -> Message {
                 let elements = match app.nav_state {
                     NavState::UpNext(_) => app.songs.songs_in_next_up(),
                     NavState::Library(_) => app.songs.songs_in_library(),
+                    NavState::Playlists(_) => app.playlists.len(),
+                    NavState::Report(_) => app.songs.report.failures.len(),
+                    NavState::Duplicates(_) => app.duplicates.iter().flatten().count(),
+                    NavState::Lyrics(_) => {
+                        app.lyrics.as_ref().map_or(0, |lyrics| lyrics.lines.len())
+                    }
                     _ => 0,
                 };
                 return Message::NavStateInnerPrev(code != KeyCode::PageUp, elements);
@@ -0,0 +1,301 @@
+use std::{fs, path::Path};
+
+use id3::{Tag, frame::TimestampFormat};
+use lofty::{file::TaggedFileExt, tag::ItemKey};
+
+use crate::songs::Song;
+
+/// Lyrics of a song, read when the lyrics pane is opened rather than kept in the library cache
+pub struct Lyrics {
+    pub lines: Vec<LyricLine>,
+    /// Whether every line has a time, so the current line can be followed during playback
+    pub synced: bool,
+}
+
+pub struct LyricLine {
+    /// When the line is sung in milliseconds, relative to the start of the song
+    pub time: Option<u64>,
+    pub text: String,
+}
+
+impl Lyrics {
+    /// Looks for a sidecar `.lrc` file next to the audio file first, then for synchronized (SYLT) and
+    /// unsynchronized (USLT) ID3 frames, or a LYRICS comment for other formats. Returns `None` if the
+    /// song has no lyrics
+    pub fn load(song: &Song) -> Option<Lyrics> {
+        let lines = Self::read_sidecar(&song.path)
+            .or_else(|| Self::read_embedded(&song.path))
+            .filter(|lines| !lines.is_empty())?;
+
+        // Tracks of a cue sheet share the lyrics of their file, so only their own part is shown
+        let lines: Vec<LyricLine> = match song.start {
+            Some(start) if lines.iter().all(|line| line.time.is_some()) => lines
+                .into_iter()
+                .filter(|line| {
+                    line.time
+                        .is_some_and(|time| time >= start && song.end.is_none_or(|end| time < end))
+                })
+                .map(|line| LyricLine {
+                    time: line.time.map(|time| time - start),
+                    text: line.text,
+                })
+                .collect(),
+            _ => lines,
+        };
+
+        Some(Lyrics {
+            synced: !lines.is_empty() && lines.iter().all(|line| line.time.is_some()),
+            lines,
+        })
+    }
+
+    /// The last line that started at or before `position` in milliseconds
+    pub fn current_line(&self, position: u64) -> Option<usize> {
+        if !self.synced {
+            return None;
+        }
+
+        self.lines
+            .iter()
+            .rposition(|line| line.time.is_some_and(|time| time <= position))
+    }
+
+    fn read_sidecar(path: &Path) -> Option<Vec<LyricLine>> {
+        let bytes = fs::read(path.with_extension("lrc")).ok()?;
+
+        // Like playlists, older .lrc files are often Latin-1
+        let contents = match String::from_utf8(bytes) {
+            Ok(contents) => contents,
+            Err(err) => err.into_bytes().into_iter().map(char::from).collect(),
+        };
+
+        Some(parse_lrc(contents.trim_start_matches('\u{feff}')))
+    }
+
+    fn read_embedded(path: &Path) -> Option<Vec<LyricLine>> {
+        let is_id3 = path.extension().is_some_and(|extension| {
+            matches!(
+                extension.to_string_lossy().to_lowercase().as_str(),
+                "mp3" | "wav" | "aiff" | "aif"
+            )
+        });
+
+        if is_id3 {
+            let tag = Tag::read_from_path(path).ok()?;
+
+            // Frames timed in MPEG frames cannot be converted without decoding, so they are skipped
+            let synced = tag
+                .synchronised_lyrics()
+                .find(|lyrics| lyrics.timestamp_format == TimestampFormat::Ms);
+            if let Some(lyrics) = synced {
+                return Some(
+                    lyrics
+                        .content
+                        .iter()
+                        .map(|(time, text)| LyricLine {
+                            time: Some(*time as u64),
+                            text: text.trim().to_owned(),
+                        })
+                        .collect(),
+                );
+            }
+
+            tag.lyrics().next().map(|lyrics| parse_lrc(&lyrics.text))
+        } else {
+            let tagged_file = lofty::read_from_path(path).ok()?;
+            let tag = tagged_file.primary_tag().or(tagged_file.first_tag())?;
+            tag.get_string(ItemKey::Lyrics).map(parse_lrc)
+        }
+    }
+}
+
+/// Parses LRC lyrics, where lines start with one or more `[mm:ss.xx]` times. Text without any times
+/// is kept as unsynchronized lyrics, since embedded lyrics can be either.
+/// See: https://en.wikipedia.org/wiki/LRC_(file_format)
+fn parse_lrc(contents: &str) -> Vec<LyricLine> {
+    let mut offset: i64 = 0;
+    let mut synced = Vec::new();
+    let mut plain = Vec::new();
+
+    for line in contents.lines() {
+        let mut rest = line.trim();
+        let mut times = Vec::new();
+
+        while let Some(tag) = rest.strip_prefix('[')
+            && let Some((tag, after)) = tag.split_once(']')
+        {
+            if let Some(time) = parse_time(tag) {
+                times.push(time);
+            } else if let Some(value) = tag.strip_prefix("offset:") {
+                offset = value.trim().parse().unwrap_or(0);
+            } else if !tag.contains(':') {
+                break;
+            }
+            rest = after.trim_start();
+        }
+
+        let text = rest.trim().to_owned();
+        if times.is_empty() {
+            // Metadata like [ar:Artist] is skipped along with the blank lines around it
+            if rest.len() < line.trim().len() && text.is_empty() {
+                continue;
+            }
+            plain.push(text);
+        } else {
+            for time in times {
+                synced.push((time, text.clone()));
+            }
+        }
+    }
+
+    if synced.is_empty() {
+        // Leading and trailing blank lines only take up space
+        let start = plain.iter().position(|line| !line.is_empty());
+        let end = plain.iter().rposition(|line| !line.is_empty());
+        return match (start, end) {
+            (Some(start), Some(end)) => plain[start..=end]
+                .iter()
+                .map(|text| LyricLine {
+                    time: None,
+                    text: text.to_owned(),
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+    }
+
+    // A positive offset shows lines earlier
+    synced.sort_by_key(|(time, _)| *time);
+    synced
+        .into_iter()
+        .map(|(time, text)| LyricLine {
+            time: Some((time as i64 - offset).max(0) as u64),
+            text,
+        })
+        .collect()
+}
+
+/// Parses `mm:ss`, `mm:ss.xx`, or `mm:ss.xxx` into milliseconds
+fn parse_time(tag: &str) -> Option<u64> {
+    let (minutes, seconds) = tag.split_once(':')?;
+    let minutes: u64 = minutes.trim().parse().ok()?;
+    let (seconds, fraction) = seconds.split_once(['.', ':']).unwrap_or((seconds, "0"));
+    let seconds: u64 = seconds.trim().parse().ok()?;
+
+    let fraction = fraction.trim();
+    if fraction.is_empty() || !fraction.chars().all(|char| char.is_ascii_digit()) {
+        return None;
+    }
+    let fraction: u64 = match fraction.len() {
+        1 => fraction.parse::<u64>().ok()? * 100,
+        2 => fraction.parse::<u64>().ok()? * 10,
+        _ => fraction.get(..3)?.parse().ok()?,
+    };
+
+    Some(minutes * 60_000 + seconds * 1000 + fraction)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn times_and_text(lines: &[LyricLine]) -> Vec<(Option<u64>, &str)> {
+        lines
+            .iter()
+            .map(|line| (line.time, line.text.as_str()))
+            .collect()
+    }
+
+    fn synced(times: &[u64]) -> Lyrics {
+        Lyrics {
+            lines: times
+                .iter()
+                .map(|time| LyricLine {
+                    time: Some(*time),
+                    text: String::new(),
+                })
+                .collect(),
+            synced: true,
+        }
+    }
+
+    #[test]
+    fn parses_times() {
+        assert_eq!(parse_time("01:02"), Some(62_000));
+        assert_eq!(parse_time("01:02.5"), Some(62_500));
+        assert_eq!(parse_time("01:02.34"), Some(62_340));
+        assert_eq!(parse_time("01:02.345"), Some(62_345));
+        assert_eq!(parse_time("01:02.3456"), Some(62_345));
+        assert_eq!(parse_time("01:02:34"), Some(62_340));
+        assert_eq!(parse_time("ar:Artist"), None);
+        assert_eq!(parse_time("01:02."), None);
+        assert_eq!(parse_time("01:02.x"), None);
+    }
+
+    #[test]
+    fn parses_lines_with_several_times() {
+        let lines = parse_lrc(
+            "[ti:Song]\n[ar:Artist]\n\n[00:10.00]First\n[00:20.00][00:40.00] Chorus \n[00:30.500]Second\n",
+        );
+
+        assert_eq!(
+            times_and_text(&lines),
+            [
+                (Some(10_000), "First"),
+                (Some(20_000), "Chorus"),
+                (Some(30_500), "Second"),
+                (Some(40_000), "Chorus"),
+            ]
+        );
+    }
+
+    #[test]
+    fn applies_offset() {
+        let lines = parse_lrc("[offset:+500]\n[00:00.20]Early\n[00:10.00]Later\n");
+        assert_eq!(
+            times_and_text(&lines),
+            [(Some(0), "Early"), (Some(9_500), "Later")]
+        );
+
+        let lines = parse_lrc("[offset:-500]\n[00:10.00]Later\n");
+        assert_eq!(times_and_text(&lines), [(Some(10_500), "Later")]);
+    }
+
+    #[test]
+    fn keeps_unsynchronized_lyrics() {
+        let lines = parse_lrc("\n\nFirst verse\n\n[Chorus]\nSecond verse\n\n");
+
+        assert_eq!(
+            times_and_text(&lines),
+            [
+                (None, "First verse"),
+                (None, ""),
+                (None, "[Chorus]"),
+                (None, "Second verse"),
+            ]
+        );
+        assert!(parse_lrc("[ar:Artist]\n\n").is_empty());
+    }
+
+    #[test]
+    fn finds_current_line() {
+        let lyrics = synced(&[1_000, 5_000, 9_000]);
+
+        assert_eq!(lyrics.current_line(0), None);
+        assert_eq!(lyrics.current_line(999), None);
+        assert_eq!(lyrics.current_line(1_000), Some(0));
+        assert_eq!(lyrics.current_line(4_999), Some(0));
+        assert_eq!(lyrics.current_line(5_000), Some(1));
+        assert_eq!(lyrics.current_line(9_000), Some(2));
+        assert_eq!(lyrics.current_line(u64::MAX), Some(2));
+    }
+
+    #[test]
+    fn has_no_current_line_without_times() {
+        let lyrics = Lyrics {
+            synced: false,
+            ..synced(&[1_000])
+        };
+        assert_eq!(lyrics.current_line(5_000), None);
+    }
+}
@@ -3,8 +3,8 @@ use color_eyre::{Result, eyre::Error};
 use crossterm::ExecutableCommand;
 use ratatui::{Terminal, prelude::CrosstermBackend};
 use std::{
-    io::{self, Stdout},
-    path::PathBuf,
+    io::{self, Stdout, Write},
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread::{self, JoinHandle},
     time::Duration,
@@ -13,17 +13,29 @@ use std::{
 use crate::{
     app::{App, AppLayout, NavState},
     files::Config,
-    songs::Songs,
+    scan::ScanProgress,
+    songs::{Song, Songs},
 };
 
 mod app;
+mod artists;
+mod cache;
+mod cue;
+mod duplicates;
 mod files;
+mod gain;
+mod infer;
 mod input;
+mod lyrics;
 #[cfg(feature = "mpris")]
 mod mpris;
 mod mpv;
+mod playlist;
+mod scan;
 mod songs;
+mod tags;
 mod utilities;
+mod watcher;
 mod widget;
 
 const MPV_SOCKET: &'static str = "/tmp/mpv-socket";
@@ -33,6 +45,12 @@ const MPV_SOCKET: &'static str = "/tmp/mpv-socket";
 struct Flags {
     #[arg(short, long)]
     dir: Option<PathBuf>,
+    /// M3U, M3U8, or PLS playlist to queue on startup
+    #[arg(short, long)]
+    playlist: Option<PathBuf>,
+    /// Measure the loudness of songs without ReplayGain tags with ffmpeg, then exit
+    #[arg(long)]
+    analyze_gain: bool,
 }
 
 fn main() -> Result<()> {
@@ -40,9 +58,26 @@ fn main() -> Result<()> {
 
     let flags = Flags::parse();
     let config = Config::with_dir(flags.dir)?;
-    let cache_path = files::cache_path()?;
-    let songs = Songs::new(&config, &cache_path)?;
-    let app = Arc::new(Mutex::new(App::new(songs, config)));
+    let cache_path = files::cache_path(config.binary_cache)?;
+    if flags.analyze_gain {
+        let mut songs = Songs::new(&config, &cache_path)?;
+        return analyze_gain(&mut songs, &config, &cache_path);
+    }
+
+    // The library is scanned in the background, so the interface shows up right away
+    let previous = Songs::read_cache(&config, &cache_path)?;
+    let mut songs = Songs::empty()?;
+    songs.seed(&config, previous.clone());
+
+    let mut app = App::new(songs, config);
+    let progress = app.begin_scan();
+    if let Some(playlist) = flags.playlist {
+        app.load_playlist(&playlist)?;
+    }
+
+    let config = app.config.clone();
+    let app = Arc::new(Mutex::new(app));
+    thread_scan(app.clone(), config, previous, progress);
     let mut handles = threads(app.clone(), ratatui::init());
 
     io::stdout().execute(crossterm::event::EnableMouseCapture)?;
@@ -62,14 +97,29 @@ fn main() -> Result<()> {
     }
 }
 
+fn analyze_gain(songs: &mut Songs, config: &Config, cache_path: &Path) -> Result<()> {
+    let (measured, failures) = songs.analyze_gain(config, cache_path, |done, total| {
+        print!("\rAnalyzing loudness: {}/{}", done, total);
+        let _ = io::stdout().flush();
+    })?;
+
+    println!("\nMeasured {} songs", measured);
+    for failure in &failures {
+        println!("{}: {}", failure.path.to_string_lossy(), failure.reason);
+    }
+
+    Ok(())
+}
+
 #[cfg(feature = "mpris")]
 fn threads(
     app: Arc<Mutex<App>>,
     terminal: Terminal<CrosstermBackend<Stdout>>,
-) -> [Option<JoinHandle<Result<()>>>; 3] {
+) -> [Option<JoinHandle<Result<()>>>; 4] {
     [
         Some(thread_draw(app.clone(), terminal)),
         Some(thread_events(app.clone())),
+        Some(watcher::thread_watcher(app.clone())),
         Some(mpris::mpris::thread_mpris(app.clone())),
     ]
 }
@@ -78,10 +128,11 @@ fn threads(
 fn threads(
     app: Arc<Mutex<App>>,
     terminal: Terminal<CrosstermBackend<Stdout>>,
-) -> [Option<JoinHandle<Result<()>>>; 3] {
+) -> [Option<JoinHandle<Result<()>>>; 4] {
     [
         Some(thread_draw(app.clone(), terminal)),
         Some(thread_events(app.clone())),
+        Some(watcher::thread_watcher(app.clone())),
         None,
     ]
 }
@@ -152,6 +203,37 @@ fn thread_events(app: Arc<Mutex<App>>) -> JoinHandle<Result<()>> {
     })
 }
 
+/// Reads the library while handing songs to the app as they are read, so they can be browsed before
+/// the scan is done. Not joined like the other threads, since finishing does not end the program
+fn thread_scan(
+    app: Arc<Mutex<App>>,
+    config: Config,
+    previous: Vec<Song>,
+    progress: Arc<ScanProgress>,
+) {
+    thread::spawn(move || {
+        let scan = {
+            let progress = progress.clone();
+            thread::spawn(move || Songs::load_songs(&config, previous, Some(&progress)))
+        };
+
+        while !scan.is_finished() {
+            thread::sleep(Duration::from_millis(500));
+
+            let partial = progress.take_partial();
+            if !partial.is_empty() {
+                App::do_once(app.clone(), |app| app.add_partial_songs(partial));
+            }
+        }
+
+        // The whole library replaces the partial results, including any not taken yet
+        let result = scan
+            .join()
+            .map_err(|_| Error::msg("Library scan stopped unexpectedly"));
+        App::do_once(app, |app| app.finish_scan(result));
+    });
+}
+
 fn generate_results<const N: usize>(
     mut result_sum: Result<()>,
     handles: [Option<JoinHandle<Result<()>>>; N],
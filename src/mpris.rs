@@ -41,7 +41,7 @@ pub mod mpris {
     fn metadata_current(app: &App) -> Option<Metadata> {
         app.songs.current_song().map(|song| {
             let mut builder = Metadata::builder()
-                .artist(vec![song.artist.clone()])
+                .artist(song.artists.clone())
                 .album(song.album.clone())
                 .genre(song.genres.clone())
                 .title(song.title.clone())
@@ -51,6 +51,22 @@ pub mod mpris {
                 builder = builder.art_url(cover);
             }
 
+            if let Some(album_artist) = &song.album_artist {
+                builder = builder.album_artist(vec![album_artist.clone()]);
+            }
+
+            if let Some(composer) = &song.composer {
+                builder = builder.composer(vec![composer.clone()]);
+            }
+
+            if let Some(disc) = song.disc {
+                builder = builder.disc_number(disc as i32);
+            }
+
+            if let Some(duration) = song.duration {
+                builder = builder.length(Time::from_secs(duration as i64));
+            }
+
             builder.build()
         })
     }
@@ -104,6 +120,7 @@ pub mod mpris {
                             | Message::PlayAll
                             | Message::SongPrevious
                             | Message::ReloadMusic
+                            | Message::ReplaceUpNext(_)
                             | Message::MoveSong => {
                                 if let Some(metadata) = metadata_current(&app) {
                                     server
@@ -255,7 +272,7 @@ pub mod mpris {
 
         async fn position(&self) -> fdo::Result<Time> {
             match MpvCommand::GetPosition.run() {
-                Ok(MpvCommandFeedback::Int(position)) => Ok(Time::from_secs(position as i64)),
+                Ok(MpvCommandFeedback::Int(position)) => Ok(Time::from_millis(position as i64)),
                 Err(err) => Err(fdo::Error::Failed(err.to_string())),
                 _ => Err(fdo::Error::Failed(
                     "Could not send internal message".to_owned(),
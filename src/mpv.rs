@@ -50,14 +50,15 @@ impl MpvCommand {
                 let json =
                     serde_json::from_str::<HashMap<String, Value>>(&Self::read_from_ipc(&cmd)?)?;
 
+                // In milliseconds, precise enough to follow synchronized lyrics
                 let progress = json
                     .get("data")
                     .ok_or(std::io::Error::other("Couldn't parse json data from IPC"))?
                     .as_f64()
                     .ok_or(std::io::Error::other("Couldn't parse json float from IPC"))?
-                    as i32;
+                    * 1000.0;
 
-                Ok(MpvCommandFeedback::Int(progress))
+                Ok(MpvCommandFeedback::Int(progress as i32))
             }
             MpvCommand::GetProgress => {
                 let cmd_now = json!({"command" : ["get_property", "playback-time"]}).to_string();
@@ -0,0 +1,431 @@
+use std::{
+    fs,
+    path::{Component, Path, PathBuf},
+};
+
+use color_eyre::eyre::Error;
+use serde::{Deserialize, Serialize};
+
+use crate::{files, songs::Song};
+
+/// A named playlist, saved as an M3U8 file in the playlists directory. Smart playlists are saved as
+/// their query in a JSON file instead, and their paths are filled in from the library
+pub struct Playlist {
+    pub name: String,
+    pub paths: Vec<PathBuf>,
+    pub smart: Option<SmartQuery>,
+}
+
+/// A saved search, in the syntax of the fuzzy finder, optionally ordered and capped
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SmartQuery {
+    pub query: String,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub order: SmartOrder,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SmartOrder {
+    /// The order of the library, as set by `sort_order`
+    #[default]
+    Library,
+    Random,
+    Artist,
+    Title,
+    Added,
+    Year,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    M3u8,
+    Xspf,
+}
+
+/// How exported playlists refer to songs, set in the config
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum ExportPaths {
+    #[default]
+    Absolute,
+    /// Relative to the library root of the songs, with the playlist written into that root, which is
+    /// how other players resolve them
+    Relative,
+}
+
+/// Reads the file paths listed in an M3U, M3U8, or PLS playlist, in order. Relative entries are
+/// resolved against the directory of the playlist
+pub fn read(playlist_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let bytes = fs::read(playlist_path)
+        .map_err(|err| Error::msg(format!("{}: {}", playlist_path.to_string_lossy(), err)))?;
+
+    // Plain .m3u files are often Latin-1, while .m3u8 is always UTF-8
+    let contents = match String::from_utf8(bytes) {
+        Ok(contents) => contents,
+        Err(err) => err.into_bytes().into_iter().map(char::from).collect(),
+    };
+    let contents = contents.trim_start_matches('\u{feff}');
+
+    let is_pls = playlist_path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("pls"));
+    let entries: Vec<&str> = if is_pls {
+        contents
+            .lines()
+            .filter_map(|line| line.trim().split_once('='))
+            .filter(|(key, _)| key.to_lowercase().starts_with("file"))
+            .map(|(_, value)| value.trim())
+            .collect()
+    } else {
+        contents
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect()
+    };
+
+    let dir = playlist_path.parent().unwrap_or(Path::new("."));
+    Ok(entries
+        .into_iter()
+        .map(|entry| match entry.strip_prefix("file://") {
+            // Only URIs are percent-encoded, plain paths may contain a literal '%'
+            Some(uri) => dir.join(uri_decode(uri.strip_prefix("localhost").unwrap_or(uri))),
+            None => dir.join(entry),
+        })
+        .collect())
+}
+
+impl Playlist {
+    pub fn load_all() -> Result<Vec<Playlist>, Error> {
+        let dir = files::playlists_path()?;
+        let mut playlists: Vec<Playlist> = fs::read_dir(&dir)
+            .map_err(|err| Error::new(err))?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter_map(|path| {
+                let name = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("m3u8") => Some(read(&path).map(|paths| Playlist {
+                        name,
+                        paths,
+                        smart: None,
+                    })),
+                    Some("json") => Some(Self::read_smart(&path).map(|smart| Playlist {
+                        name,
+                        paths: Vec::new(),
+                        smart: Some(smart),
+                    })),
+                    _ => None,
+                }
+            })
+            .collect::<Result<Vec<Playlist>, Error>>()?;
+
+        playlists.sort_by(|first, second| first.name.cmp(&second.name));
+        Ok(playlists)
+    }
+
+    fn read_smart(path: &Path) -> Result<SmartQuery, Error> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| Error::msg(format!("{}: {}", path.to_string_lossy(), err)))?;
+        serde_json::from_str(&contents)
+            .map_err(|err| Error::msg(format!("{}: {}", path.to_string_lossy(), err)))
+    }
+
+    pub fn create(
+        name: &str,
+        smart: Option<SmartQuery>,
+        playlists: &[Playlist],
+    ) -> Result<Playlist, Error> {
+        let playlist = Playlist {
+            name: Self::validate_name(name, playlists)?,
+            paths: Vec::new(),
+            smart,
+        };
+        playlist.save()?;
+        Ok(playlist)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let contents = match &self.smart {
+            Some(smart) => serde_json::to_string_pretty(smart).map_err(|err| Error::new(err))?,
+            None => {
+                let mut contents = String::from("#EXTM3U\n");
+                for path in &self.paths {
+                    contents.push_str(&path.to_string_lossy());
+                    contents.push('\n');
+                }
+                contents
+            }
+        };
+
+        fs::write(self.file_path(&self.name)?, contents).map_err(|err| Error::new(err))
+    }
+
+    pub fn rename(&mut self, name: &str, playlists: &[Playlist]) -> Result<(), Error> {
+        let name = Self::validate_name(name, playlists)?;
+        fs::rename(self.file_path(&self.name)?, self.file_path(&name)?)
+            .map_err(|err| Error::new(err))?;
+        self.name = name;
+        Ok(())
+    }
+
+    pub fn delete(&self) -> Result<(), Error> {
+        fs::remove_file(self.file_path(&self.name)?).map_err(|err| Error::new(err))
+    }
+
+    fn file_path(&self, name: &str) -> Result<PathBuf, Error> {
+        let extension = if self.smart.is_some() { "json" } else { "m3u8" };
+        Ok(files::playlists_path()?.join(format!("{}.{}", name, extension)))
+    }
+
+    /// Names become file names, so they cannot contain path separators or be taken already
+    fn validate_name(name: &str, playlists: &[Playlist]) -> Result<String, Error> {
+        let name = name.trim();
+
+        if name.is_empty() || name.starts_with('.') {
+            Err(Error::msg(
+                "Playlist names cannot be empty or start with a '.'",
+            ))
+        } else if name.contains(['/', '\\']) {
+            Err(Error::msg("Playlist names cannot contain '/' or '\\'"))
+        } else if playlists
+            .iter()
+            .any(|playlist| playlist.name.eq_ignore_ascii_case(name))
+        {
+            Err(Error::msg(format!(
+                "A playlist named '{}' already exists",
+                name
+            )))
+        } else {
+            Ok(name.to_owned())
+        }
+    }
+}
+
+impl SmartQuery {
+    /// Steps through no limit and a few common caps
+    pub fn next_limit(&self) -> Option<usize> {
+        match self.limit {
+            None => Some(25),
+            Some(current) => [25, 50, 100, 250]
+                .into_iter()
+                .find(|limit| *limit > current),
+        }
+    }
+
+    /// Short summary shown next to the playlist name, e.g. "50, Random"
+    pub fn describe(&self) -> String {
+        match self.limit {
+            Some(limit) => format!("{}, {}", limit, self.order.name()),
+            None => self.order.name().to_owned(),
+        }
+    }
+}
+
+impl SmartOrder {
+    pub fn next(self) -> SmartOrder {
+        match self {
+            SmartOrder::Library => SmartOrder::Random,
+            SmartOrder::Random => SmartOrder::Artist,
+            SmartOrder::Artist => SmartOrder::Title,
+            SmartOrder::Title => SmartOrder::Added,
+            SmartOrder::Added => SmartOrder::Year,
+            SmartOrder::Year => SmartOrder::Library,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SmartOrder::Library => "Library Order",
+            SmartOrder::Random => "Random",
+            SmartOrder::Artist => "Artist",
+            SmartOrder::Title => "Title",
+            SmartOrder::Added => "Recently Added",
+            SmartOrder::Year => "Year",
+        }
+    }
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::M3u8 => "m3u8",
+            ExportFormat::Xspf => "xspf",
+        }
+    }
+}
+
+/// Writes the songs to `{name}.m3u8` or `{name}.xspf`, returning its path. Exports with relative
+/// paths go into the library root of the first song so they move along with the library, others
+/// into the exports directory. Tracks split from a cue sheet are left out, as neither format can
+/// play part of a file
+pub fn export(
+    name: &str,
+    songs: &[&Song],
+    format: ExportFormat,
+    paths: ExportPaths,
+) -> Result<PathBuf, Error> {
+    let songs: Vec<&Song> = songs
+        .iter()
+        .filter(|song| song.cue.is_none())
+        .copied()
+        .collect();
+    let export_dir = match songs.first() {
+        Some(song) if paths == ExportPaths::Relative && song.root.is_dir() => song.root.clone(),
+        _ => files::exports_path()?,
+    };
+    let contents = match format {
+        ExportFormat::M3u8 => to_m3u8(&songs, paths, &export_dir),
+        ExportFormat::Xspf => to_xspf(name, &songs, paths, &export_dir),
+    };
+
+    let export_path = export_dir.join(format!("{}.{}", name, format.extension()));
+    fs::write(&export_path, contents).map_err(|err| Error::new(err))?;
+    Ok(export_path)
+}
+
+fn export_path(song: &Song, paths: ExportPaths, export_dir: &Path) -> PathBuf {
+    match paths {
+        ExportPaths::Absolute => song.path.clone(),
+        ExportPaths::Relative => relative_path(&song.path, export_dir),
+    }
+}
+
+/// The path of `path` as seen from `dir`, climbing out of `dir` with `..` as far as needed. Paths
+/// without anything in common (like another drive on Windows) stay absolute
+fn relative_path(path: &Path, dir: &Path) -> PathBuf {
+    let common = path
+        .components()
+        .zip(dir.components())
+        .take_while(|(first, second)| first == second)
+        .count();
+
+    if common == 0 {
+        return path.to_owned();
+    }
+
+    dir.components()
+        .skip(common)
+        .map(|_| Component::ParentDir)
+        .chain(path.components().skip(common))
+        .collect()
+}
+
+fn to_m3u8(songs: &[&Song], paths: ExportPaths, export_dir: &Path) -> String {
+    let mut contents = String::from("#EXTM3U\n");
+    for song in songs {
+        // -1 is the conventional length for unknown durations
+        let duration = song.duration.map(|duration| duration as i64).unwrap_or(-1);
+        contents.push_str(&format!(
+            "#EXTINF:{},{} - {}\n{}\n",
+            duration,
+            song.artists_joined(),
+            song.title,
+            export_path(song, paths, export_dir).to_string_lossy()
+        ));
+    }
+
+    contents
+}
+
+/// See: https://www.xspf.org/spec
+fn to_xspf(name: &str, songs: &[&Song], paths: ExportPaths, export_dir: &Path) -> String {
+    let mut contents = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<playlist version=\"1\" xmlns=\"http://xspf.org/ns/0/\">\n",
+    );
+    contents.push_str(&format!(
+        "  <title>{}</title>\n  <trackList>\n",
+        xml_escape(name)
+    ));
+
+    for song in songs {
+        let path = export_path(song, paths, export_dir);
+        let location = if path.is_absolute() {
+            format!("file://{}", uri_encode(&path))
+        } else {
+            uri_encode(&path)
+        };
+
+        contents.push_str("    <track>\n");
+        contents.push_str(&format!(
+            "      <location>{}</location>\n",
+            xml_escape(&location)
+        ));
+        contents.push_str(&format!(
+            "      <title>{}</title>\n",
+            xml_escape(&song.title)
+        ));
+        contents.push_str(&format!(
+            "      <creator>{}</creator>\n",
+            xml_escape(&song.artists_joined())
+        ));
+        contents.push_str(&format!(
+            "      <album>{}</album>\n",
+            xml_escape(&song.album)
+        ));
+        if let Ok(track) = song.track.parse::<u32>() {
+            contents.push_str(&format!("      <trackNum>{}</trackNum>\n", track));
+        }
+        if let Some(duration) = song.duration {
+            contents.push_str(&format!("      <duration>{}</duration>\n", duration * 1000));
+        }
+        contents.push_str("    </track>\n");
+    }
+
+    contents.push_str("  </trackList>\n</playlist>\n");
+    contents
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Decodes `%XX` escapes, keeping the entry as written if it does not decode to UTF-8
+fn uri_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .filter(|_| bytes[i] == b'%')
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8(decoded).unwrap_or(uri.to_owned())
+}
+
+/// Percent-encodes everything but unreserved characters and path separators
+fn uri_encode(path: &Path) -> String {
+    path.to_string_lossy()
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
@@ -0,0 +1,334 @@
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex, OnceLock,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Error;
+use glob::Pattern;
+
+use crate::{cue::CueSheet, files::Config, songs::Song};
+
+const IGNORE_FILE: &str = ".auditoriumignore";
+
+#[derive(Debug, Clone)]
+pub struct ScanFailure {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub failures: Vec<ScanFailure>,
+}
+
+/// Decides which files of a music directory are scanned based on the configured
+/// extensions, exclude patterns, depth limit, and `.auditoriumignore` files
+pub struct ScanRules<'a> {
+    extensions: &'a [String],
+    exclude: &'a [Pattern],
+    max_depth: Option<usize>,
+    skip_hidden: bool,
+    progress: Option<&'a ScanProgress>,
+}
+
+/// Progress of a scan running in the background, shared with the UI
+#[derive(Default)]
+pub struct ScanProgress {
+    /// Song files found so far while walking the library roots
+    pub found: AtomicUsize,
+    /// Files read, or taken from the cache, so far
+    pub probed: AtomicUsize,
+    pub failed: AtomicUsize,
+    probing_since: OnceLock<Instant>,
+    /// Songs read since the UI last took them, so they can be browsed before the scan is done
+    partial: Mutex<Vec<Song>>,
+}
+
+/// State of a single traversal, which may follow symlinks back into directories it already visited
+struct Walk<'a> {
+    root: &'a Path,
+    ignored: Vec<IgnoreRule>,
+    visited: HashSet<PathBuf>,
+    song_paths: &'a mut Vec<PathBuf>,
+    skipped: &'a mut Vec<ScanFailure>,
+}
+
+struct IgnoreRule {
+    base: PathBuf,
+    pattern: Pattern,
+}
+
+impl ScanFailure {
+    pub fn new(path: &Path, reason: impl ToString) -> ScanFailure {
+        ScanFailure {
+            path: path.to_owned(),
+            reason: reason.to_string().replace('\n', " "),
+        }
+    }
+}
+
+impl ScanReport {
+    pub fn new(failures: Vec<ScanFailure>) -> ScanReport {
+        ScanReport { failures }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Replaces the failures recorded under `changed` paths with the results of a partial scan
+    pub fn merge(&mut self, changed: &[PathBuf], failures: Vec<ScanFailure>) {
+        self.failures
+            .retain(|failure| !changed.iter().any(|path| failure.path.starts_with(path)));
+        self.failures.extend(failures);
+        self.failures
+            .sort_by(|first, second| first.path.cmp(&second.path));
+    }
+
+    pub fn write_log(&self, log_path: &Path) -> Result<(), Error> {
+        let log: String = self
+            .failures
+            .iter()
+            .map(|failure| format!("{}: {}\n", failure.path.to_string_lossy(), failure.reason))
+            .collect();
+
+        fs::write(log_path, log).map_err(|err| Error::new(err))
+    }
+
+    pub fn summary(&self) -> Option<String> {
+        match self.failures.len() {
+            0 => None,
+            1 => Some("1 path could not be scanned, press [e] to view it".to_owned()),
+            count => Some(format!(
+                "{} paths could not be scanned, press [e] to view them",
+                count
+            )),
+        }
+    }
+}
+
+impl ScanProgress {
+    /// Walking is done once probing starts, so from then on `found` is the total
+    pub fn start_probing(&self) {
+        let _ = self.probing_since.set(Instant::now());
+    }
+
+    pub fn add_probed(&self, songs: &[Song], files: usize, failed: usize) {
+        self.probed.fetch_add(files, Ordering::Relaxed);
+        self.failed.fetch_add(failed, Ordering::Relaxed);
+        if !songs.is_empty()
+            && let Ok(mut partial) = self.partial.lock()
+        {
+            partial.extend_from_slice(songs);
+        }
+    }
+
+    pub fn take_partial(&self) -> Vec<Song> {
+        self.partial
+            .lock()
+            .map(|mut partial| std::mem::take(&mut *partial))
+            .unwrap_or_default()
+    }
+
+    /// Estimated from how long the files probed so far took
+    pub fn eta(&self) -> Option<Duration> {
+        let since = self.probing_since.get()?;
+        let probed = self.probed.load(Ordering::Relaxed);
+        let remaining = self.found.load(Ordering::Relaxed).saturating_sub(probed);
+        (probed > 0).then(|| since.elapsed().mul_f64(remaining as f64 / probed as f64))
+    }
+}
+
+impl<'a> ScanRules<'a> {
+    pub fn new(config: &'a Config) -> ScanRules<'a> {
+        ScanRules {
+            extensions: &config.extensions,
+            exclude: config.exclude_patterns(),
+            max_depth: config.max_depth,
+            skip_hidden: config.skip_hidden,
+            progress: None,
+        }
+    }
+
+    /// Counts the song files found into `progress` while walking
+    pub fn with_progress(self, progress: Option<&'a ScanProgress>) -> ScanRules<'a> {
+        ScanRules { progress, ..self }
+    }
+
+    /// Collects the song files at or below `path`, which must be inside of `root`. Directories that
+    /// are skipped for safety (symlink loops, the depth limit) are added to `skipped`
+    pub fn collect(
+        &self,
+        root: &Path,
+        path: &Path,
+        song_paths: &mut Vec<PathBuf>,
+        skipped: &mut Vec<ScanFailure>,
+    ) {
+        let ignored: Vec<IgnoreRule> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|ancestor| ancestor.starts_with(root))
+            .collect::<Vec<&Path>>()
+            .into_iter()
+            .rev()
+            .flat_map(Self::ignore_file)
+            .collect();
+
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        if path != root
+            && (self.is_excluded(root, path, &ignored)
+                || relative
+                    .components()
+                    .any(|component| self.is_hidden(component.as_os_str())))
+        {
+            return;
+        }
+
+        let mut walk = Walk {
+            root,
+            ignored,
+            visited: HashSet::new(),
+            song_paths,
+            skipped,
+        };
+
+        if path.is_dir() {
+            self.walk(&mut walk, path, relative.components().count());
+        } else if self.is_song(path) {
+            walk.song_paths.push(path.to_owned());
+        }
+    }
+
+    fn walk(&self, walk: &mut Walk, dir: &Path, depth: usize) {
+        // Canonical paths are the same however a directory was reached, which breaks symlink loops
+        let canonical = fs::canonicalize(dir).unwrap_or(dir.to_owned());
+        if !walk.visited.insert(canonical) {
+            walk.skipped.push(ScanFailure::new(
+                dir,
+                "Directory was already scanned through another path (symlink loop?)",
+            ));
+            return;
+        }
+
+        let inherited = walk.ignored.len();
+        walk.ignored.extend(Self::ignore_file(dir));
+
+        if let Ok(child) = dir.read_dir() {
+            child.for_each(|child_result| {
+                let Ok(child) = child_result else {
+                    return;
+                };
+
+                let child_path = child.path();
+                if self.is_hidden(&child.file_name())
+                    || self.is_excluded(walk.root, &child_path, &walk.ignored)
+                {
+                    return;
+                }
+
+                if child_path.is_dir()
+                    && let Some(max_depth) = self.max_depth
+                    && depth >= max_depth
+                {
+                    walk.skipped.push(ScanFailure::new(
+                        &child_path,
+                        format!(
+                            "Directory is deeper than the maximum depth of {}",
+                            max_depth
+                        ),
+                    ));
+                } else if child_path.is_dir() {
+                    self.walk(walk, &child_path, depth + 1);
+                } else if self.is_song(&child_path) {
+                    walk.song_paths.push(child_path);
+                    if let Some(progress) = self.progress {
+                        progress.found.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+            });
+        }
+
+        walk.ignored.truncate(inherited);
+    }
+
+    fn is_hidden(&self, name: &OsStr) -> bool {
+        self.skip_hidden && name.to_string_lossy().starts_with('.')
+    }
+
+    /// Changing an ignore file affects the whole directory it is in, and so does changing a cue
+    /// sheet or any file next to one, since the sheet and the files it splits are read together
+    pub fn affected_path(path: &Path) -> PathBuf {
+        let Some(parent) = path.parent() else {
+            return path.to_owned();
+        };
+
+        let next_to_cue = || {
+            parent.read_dir().is_ok_and(|mut entries| {
+                entries.any(|entry| entry.is_ok_and(|entry| CueSheet::is_cue(&entry.path())))
+            })
+        };
+
+        if path.file_name().is_some_and(|name| name == IGNORE_FILE)
+            || CueSheet::is_cue(path)
+            || (!path.is_dir() && next_to_cue())
+        {
+            parent.to_owned()
+        } else {
+            path.to_owned()
+        }
+    }
+
+    /// Cue sheets are always scanned, regardless of the allowed extensions
+    fn is_song(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() || CueSheet::is_cue(path) {
+            return true;
+        }
+
+        path.extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .is_some_and(|extension| {
+                self.extensions
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(&extension))
+            })
+    }
+
+    /// Patterns match either the path relative to where they were defined or the bare file name
+    fn is_excluded(&self, root: &Path, path: &Path, ignored: &[IgnoreRule]) -> bool {
+        let name = path.file_name().map(|name| name.to_string_lossy());
+        let matches = |pattern: &Pattern, base: &Path| {
+            path.strip_prefix(base)
+                .is_ok_and(|relative| pattern.matches_path(relative))
+                || name.as_ref().is_some_and(|name| pattern.matches(name))
+        };
+
+        self.exclude.iter().any(|pattern| matches(pattern, root))
+            || ignored
+                .iter()
+                .any(|rule| matches(&rule.pattern, &rule.base))
+    }
+
+    fn ignore_file(dir: &Path) -> Vec<IgnoreRule> {
+        let Ok(contents) = fs::read_to_string(dir.join(IGNORE_FILE)) else {
+            return Vec::new();
+        };
+
+        contents
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| Pattern::new(line.trim_end_matches('/')).ok())
+            .map(|pattern| IgnoreRule {
+                base: dir.to_owned(),
+                pattern,
+            })
+            .collect()
+    }
+}
@@ -1,22 +1,45 @@
 use std::{
-    fs::File,
-    io::{self, BufReader, Write},
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{self, Write},
+    iter,
     option::Option,
     path::{Path, PathBuf},
     process::{Child, Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::UNIX_EPOCH,
 };
 
 use color_eyre::eyre::Error;
 use ffprobe::FfProbeError;
 use id3::{Tag, TagLike, partial_tag_ok};
+use lofty::{
+    config::ParseOptions,
+    file::{AudioFile, FileType, TaggedFile, TaggedFileExt},
+    picture::PictureType,
+    probe::Probe,
+    tag::{Accessor, ItemKey},
+};
 use random_number::rand::{self, seq::SliceRandom};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
 
-use crate::{MPV_SOCKET, files};
-use crate::{app::SongLoadingState, files::Config};
+use crate::{MPV_SOCKET, files, utilities};
+use crate::{
+    app::SongLoadingState,
+    artists::ArtistRules,
+    cache::LibraryCache,
+    cue::{CueFile, CueSheet},
+    duplicates::{self, ContentHashes, HashedFile},
+    files::Config,
+    gain::{self, ReplayGainMode},
+    infer::{PathTemplates, TagFields},
+    playlist::{SmartOrder, SmartQuery},
+    scan::{ScanFailure, ScanProgress, ScanReport, ScanRules},
+};
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Default, Deserialize, Serialize, Clone)]
 pub struct Song {
     pub id: u64,
     pub title: String,
@@ -26,6 +49,75 @@ pub struct Song {
     pub track: String,
     pub path: PathBuf,
     pub cover: Option<String>,
+    /// Individual artists credited in `artist`, derived from the configured separators and aliases
+    #[serde(skip)]
+    pub artists: Vec<String>,
+    #[serde(default)]
+    pub album_artist: Option<String>,
+    #[serde(default)]
+    pub year: Option<u32>,
+    #[serde(default)]
+    pub disc: Option<u32>,
+    #[serde(default)]
+    pub composer: Option<String>,
+    /// Length in seconds
+    #[serde(default)]
+    pub duration: Option<u64>,
+    #[serde(default)]
+    pub codec: Option<String>,
+    /// Bitrate in kbps
+    #[serde(default)]
+    pub bitrate: Option<u32>,
+    /// Sample rate in Hz
+    #[serde(default)]
+    pub sample_rate: Option<u32>,
+    /// Sort names like "Beatles, The", used instead of the displayed names when sorting
+    #[serde(default)]
+    pub artist_sort: Option<String>,
+    #[serde(default)]
+    pub album_artist_sort: Option<String>,
+    #[serde(default)]
+    pub album_sort: Option<String>,
+    /// ReplayGain adjustments in dB, read from tags or measured with `--analyze-gain`
+    #[serde(default)]
+    pub track_gain: Option<f32>,
+    #[serde(default)]
+    pub album_gain: Option<f32>,
+    /// Peak amplitudes, 1.0 being full scale
+    #[serde(default)]
+    pub track_peak: Option<f32>,
+    #[serde(default)]
+    pub album_peak: Option<f32>,
+    /// Fields the tags left out, which are filled in from the path templates or with placeholders
+    #[serde(default)]
+    pub missing: TagFields,
+    /// Which of the missing fields were filled in from the path, derived from `missing` on load
+    #[serde(skip)]
+    pub inferred: TagFields,
+    /// When the song first appeared in the library (seconds since the Unix epoch)
+    #[serde(default)]
+    pub added: u64,
+    /// Songs from a cue sheet only play part of their file, from `start` to `end` in milliseconds
+    #[serde(default)]
+    pub start: Option<u64>,
+    #[serde(default)]
+    pub end: Option<u64>,
+    #[serde(default)]
+    pub cue: Option<PathBuf>,
+    #[serde(default)]
+    pub root: PathBuf,
+    #[serde(default)]
+    pub modified: u64,
+    #[serde(default)]
+    pub size: u64,
+}
+
+/// Created, modified or removed files and directories, which are read again without holding the app
+/// since probing them can take a while
+pub struct LibraryChanges {
+    paths: Vec<PathBuf>,
+    songs: Vec<Song>,
+    failures: Vec<ScanFailure>,
 }
 
 pub struct ActiveSong {
@@ -43,6 +135,22 @@ pub struct Songs {
     songs_next: Vec<usize>,
     songs_history: Vec<usize>,
     active: ActiveSong,
+    pub report: ScanReport,
+    /// IDs of lesser duplicate copies, which are left out of shuffled queues
+    hidden: HashSet<u64>,
+    /// Library positions by ID while a background scan merges its songs in, dropped whenever the
+    /// library is replaced
+    scan_positions: Option<HashMap<u64, usize>>,
+}
+
+/// How the library is ordered, changed in the config or cycled at runtime
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum SortOrder {
+    #[default]
+    Artist,
+    Title,
+    Added,
+    Year,
 }
 
 pub enum SongList {
@@ -59,9 +167,13 @@ impl ActiveSong {
         }
     }
 
-    fn with_song(song: Option<&Song>, load_cover: bool) -> Result<ActiveSong, io::Error> {
+    fn with_song(
+        song: Option<&Song>,
+        load_cover: bool,
+        replay_gain: ReplayGainMode,
+    ) -> Result<ActiveSong, io::Error> {
         let child = match song {
-            Some(song) => Some(song.play_single()?),
+            Some(song) => Some(song.play_single(replay_gain)?),
             None => None,
         };
 
@@ -86,8 +198,9 @@ impl ActiveSong {
                         return None;
                     };
 
-                    let Ok(Ok(cover)) =
-                        ImageReader::open(PathBuf::from(cover)).map(|image| image.decode())
+                    let Ok(Ok(cover)) = ImageReader::open(PathBuf::from(cover))
+                        .and_then(|image| image.with_guessed_format())
+                        .map(|image| image.decode())
                     else {
                         return None;
                     };
@@ -154,45 +267,93 @@ impl SongList {
     }
 }
 
+impl SortOrder {
+    pub fn next(self) -> SortOrder {
+        match self {
+            SortOrder::Artist => SortOrder::Title,
+            SortOrder::Title => SortOrder::Added,
+            SortOrder::Added => SortOrder::Year,
+            SortOrder::Year => SortOrder::Artist,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortOrder::Artist => "Artist",
+            SortOrder::Title => "Title",
+            SortOrder::Added => "Recently Added",
+            SortOrder::Year => "Year",
+        }
+    }
+}
+
 impl Song {
-    fn new(file_name: &Path) -> Result<Song, Error> {
+    /// Cover art is best-effort, so covers that cannot be read or stored are added to `skipped` and
+    /// the song is read without one
+    fn new(file_name: &Path, skipped: &mut Vec<ScanFailure>) -> Result<Song, Error> {
+        let mut song = if Self::has_native_tags(file_name)
+            && let Ok(song) = Self::from_tagged_file(file_name, skipped)
+        {
+            song
+        } else {
+            Self::from_id3(file_name, skipped)?
+        };
+
+        if song.cover.is_none() {
+            song.cover = Self::sidecar_cover(file_name, skipped);
+        }
+
+        Ok(song)
+    }
+
+    fn has_native_tags(file_name: &Path) -> bool {
+        file_name
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .is_some_and(|extension| {
+                matches!(
+                    extension.as_str(),
+                    "flac" | "ogg" | "oga" | "opus" | "spx" | "m4a" | "m4b" | "mp4" | "aac"
+                )
+            })
+    }
+
+    fn from_id3(file_name: &Path, skipped: &mut Vec<ScanFailure>) -> Result<Song, Error> {
         let path = file_name.to_owned();
         let tags = Tag::read_from_path(file_name);
         let Ok(tags) = partial_tag_ok(tags) else {
             return Self::from_ffprobe(file_name).map_err(|err| Error::new(err));
         };
 
-        let title = tags.title().unwrap_or("Unknown").to_owned();
+        // Missing fields stay empty, to be inferred from the path or given placeholders later
+        let title = tags.title().unwrap_or_default().to_owned();
         let genres = tags
             .genres()
             .map(|vec| {
                 if vec.first().map(|first| first.is_empty()).unwrap_or(true) {
-                    vec!["Unknown".to_owned()]
+                    Vec::new()
                 } else {
                     vec.into_iter().map(|str| str.to_owned()).collect()
                 }
             })
-            .unwrap_or(vec!["Unknown".to_owned()]);
-        let artist = tags.artist().unwrap_or("Unknown").to_owned();
-        let album = tags.album().unwrap_or("Single").to_owned();
+            .unwrap_or_default();
+        let artist = tags.artist().unwrap_or_default().to_owned();
+        let album = tags.album().unwrap_or_default().to_owned();
         let track = tags
             .track()
             .map(|track| track.to_string())
-            .unwrap_or("1".to_owned());
-        let id = rand::random::<u64>();
-
-        let cover =
-            if let Some(picture) = tags.pictures().next().map(|picture| picture.data.clone()) {
-                let mut cover_path = files::art_path()?;
-                cover_path.push(format!("{}.jpg", &id));
-                let mut file = File::create(&cover_path)?;
-                file.write_all(&picture)?;
-                Some(cover_path.to_string_lossy().to_string())
-            } else {
-                None
-            };
+            .unwrap_or_default();
+        let id = Self::id_from_path(file_name);
+
+        let picture = tags
+            .pictures()
+            .find(|picture| picture.picture_type == id3::frame::PictureType::CoverFront)
+            .or(tags.pictures().next());
+        let cover = picture.and_then(|picture| {
+            Self::skip_cover(Self::write_cover(&picture.data), file_name, skipped)
+        });
 
-        let song = Ok(Song {
+        let mut song = Song {
             id,
             title,
             genres,
@@ -201,21 +362,351 @@ impl Song {
             track,
             path,
             cover,
+            album_artist: tags.album_artist().map(|artist| artist.to_owned()),
+            year: tags
+                .year()
+                .or(tags.date_recorded().map(|date| date.year))
+                .and_then(|year| u32::try_from(year).ok()),
+            disc: tags.disc(),
+            composer: tags
+                .text_for_frame_id("TCOM")
+                .map(|composer| composer.to_owned()),
+            artist_sort: tags.text_for_frame_id("TSOP").map(|name| name.to_owned()),
+            album_artist_sort: tags.text_for_frame_id("TSO2").map(|name| name.to_owned()),
+            album_sort: tags.text_for_frame_id("TSOA").map(|name| name.to_owned()),
+            ..Default::default()
+        };
+
+        // ReplayGain is stored in user defined text frames
+        for text in tags.extended_texts() {
+            match text.description.to_uppercase().as_str() {
+                "REPLAYGAIN_TRACK_GAIN" => song.track_gain = gain::parse_gain(&text.value),
+                "REPLAYGAIN_ALBUM_GAIN" => song.album_gain = gain::parse_gain(&text.value),
+                "REPLAYGAIN_TRACK_PEAK" => song.track_peak = gain::parse_peak(&text.value),
+                "REPLAYGAIN_ALBUM_PEAK" => song.album_peak = gain::parse_peak(&text.value),
+                _ => {}
+            }
+        }
+
+        // ID3 does not describe the audio stream itself
+        let properties = Probe::open(file_name)
+            .map(|probe| probe.options(ParseOptions::new().read_tags(false)))
+            .and_then(|probe| probe.read());
+        if let Ok(tagged_file) = properties {
+            song.read_properties(&tagged_file);
+        }
+
+        Ok(song)
+    }
+
+    /// Reads Vorbis comments (FLAC, Ogg, Opus) and MP4 atoms in-process
+    fn from_tagged_file(file_name: &Path, skipped: &mut Vec<ScanFailure>) -> Result<Song, Error> {
+        let tagged_file = lofty::read_from_path(file_name)?;
+        let tags = tagged_file
+            .primary_tag()
+            .or(tagged_file.first_tag())
+            .ok_or(Error::msg("No tags found"))?;
+
+        let title = tags
+            .title()
+            .map(|title| title.into_owned())
+            .unwrap_or_default();
+        let genres: Vec<String> = tags
+            .get_strings(ItemKey::Genre)
+            .flat_map(|genre| genre.split(';'))
+            .map(|genre| genre.trim())
+            .filter(|genre| !genre.is_empty())
+            .map(|genre| genre.to_owned())
+            .collect();
+        let artist = tags
+            .artist()
+            .map(|artist| artist.into_owned())
+            .unwrap_or_default();
+        let album = tags
+            .album()
+            .map(|album| album.into_owned())
+            .unwrap_or_default();
+        let track = tags
+            .track()
+            .map(|track| track.to_string())
+            .unwrap_or_default();
+        let id = Self::id_from_path(file_name);
+
+        let picture = tags
+            .pictures()
+            .iter()
+            .find(|picture| picture.pic_type() == PictureType::CoverFront)
+            .or(tags.pictures().first());
+        let cover = picture.and_then(|picture| {
+            Self::skip_cover(Self::write_cover(picture.data()), file_name, skipped)
+        });
+
+        let mut song = Song {
+            id,
+            title,
+            genres,
+            artist,
+            album,
+            track,
+            path: file_name.to_owned(),
+            cover,
+            album_artist: tags
+                .get_string(ItemKey::AlbumArtist)
+                .map(|artist| artist.to_owned()),
+            year: tags.date().map(|date| date.year as u32),
+            disc: tags.disk(),
+            composer: tags
+                .get_string(ItemKey::Composer)
+                .map(|composer| composer.to_owned()),
+            artist_sort: tags
+                .get_string(ItemKey::TrackArtistSortOrder)
+                .map(|name| name.to_owned()),
+            album_artist_sort: tags
+                .get_string(ItemKey::AlbumArtistSortOrder)
+                .map(|name| name.to_owned()),
+            album_sort: tags
+                .get_string(ItemKey::AlbumTitleSortOrder)
+                .map(|name| name.to_owned()),
+            track_gain: tags
+                .get_string(ItemKey::ReplayGainTrackGain)
+                .and_then(gain::parse_gain)
+                .or(tags
+                    .get_string(ItemKey::R128TrackGain)
+                    .and_then(gain::parse_r128_gain)),
+            album_gain: tags
+                .get_string(ItemKey::ReplayGainAlbumGain)
+                .and_then(gain::parse_gain)
+                .or(tags
+                    .get_string(ItemKey::R128AlbumGain)
+                    .and_then(gain::parse_r128_gain)),
+            track_peak: tags
+                .get_string(ItemKey::ReplayGainTrackPeak)
+                .and_then(gain::parse_peak),
+            album_peak: tags
+                .get_string(ItemKey::ReplayGainAlbumPeak)
+                .and_then(gain::parse_peak),
+            ..Default::default()
+        };
+
+        song.read_properties(&tagged_file);
+        Ok(song)
+    }
+
+    fn read_properties(&mut self, tagged_file: &TaggedFile) {
+        let properties = tagged_file.properties();
+
+        self.duration = Some(properties.duration().as_secs()).filter(|duration| *duration > 0);
+        self.bitrate = properties.audio_bitrate().or(properties.overall_bitrate());
+        self.sample_rate = properties.sample_rate();
+        self.codec = Some(
+            match tagged_file.file_type() {
+                FileType::Aac => "AAC",
+                FileType::Aiff => "AIFF",
+                FileType::Ape => "APE",
+                FileType::Flac => "FLAC",
+                FileType::Mpeg => "MP3",
+                FileType::Mp4 => "MP4",
+                FileType::Mpc => "Musepack",
+                FileType::Opus => "Opus",
+                FileType::Vorbis => "Vorbis",
+                FileType::Speex => "Speex",
+                FileType::Wav => "WAV",
+                FileType::WavPack => "WavPack",
+                FileType::Custom(name) => name,
+                _ => "Unknown",
+            }
+            .to_owned(),
+        );
+    }
+
+    /// Covers are named by their content, so every track of an album shares a single image
+    fn write_cover(data: &[u8]) -> Result<String, Error> {
+        let mut cover_path = files::art_path()?;
+        cover_path.push(format!(
+            "{:016x}.{}",
+            utilities::hash_stable(data),
+            Self::cover_extension(data)
+        ));
+
+        match File::options()
+            .write(true)
+            .create_new(true)
+            .open(&cover_path)
+        {
+            Ok(mut file) => file.write_all(data)?,
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(Error::new(err)),
+        }
+
+        Ok(cover_path.to_string_lossy().to_string())
+    }
+
+    /// Embedded pictures are often mislabeled, so the format is taken from the data itself
+    fn cover_extension(data: &[u8]) -> &'static str {
+        match data {
+            [0x89, b'P', b'N', b'G', ..] => "png",
+            [b'G', b'I', b'F', b'8', ..] => "gif",
+            [
+                b'R',
+                b'I',
+                b'F',
+                b'F',
+                _,
+                _,
+                _,
+                _,
+                b'W',
+                b'E',
+                b'B',
+                b'P',
+                ..,
+            ] => "webp",
+            [b'B', b'M', ..] => "bmp",
+            _ => "jpg",
+        }
+    }
+
+    /// Looks for a `cover.*`, `folder.*`, or `front.*` image next to songs without embedded art
+    fn sidecar_cover(file_name: &Path, skipped: &mut Vec<ScanFailure>) -> Option<String> {
+        let Some(Ok(entries)) = file_name.parent().map(|dir| dir.read_dir()) else {
+            return None;
+        };
+
+        let mut sidecars: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                let stem = path
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_lowercase());
+                let extension = path
+                    .extension()
+                    .map(|extension| extension.to_string_lossy().to_lowercase());
+
+                matches!(stem.as_deref(), Some("cover" | "folder" | "front"))
+                    && matches!(
+                        extension.as_deref(),
+                        Some("jpg" | "jpeg" | "png" | "webp" | "gif" | "bmp")
+                    )
+            })
+            .collect();
+
+        // Prefer cover over folder over front, regardless of directory order
+        sidecars.sort_by_key(|path| {
+            match path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_lowercase())
+                .as_deref()
+            {
+                Some("cover") => 0,
+                Some("folder") => 1,
+                _ => 2,
+            }
         });
 
-        return song;
+        let sidecar = sidecars.first()?;
+        let cover = fs::read(sidecar)
+            .map_err(|err| Error::new(err))
+            .and_then(|data| Self::write_cover(&data));
+        Self::skip_cover(cover, sidecar, skipped)
+    }
+
+    fn skip_cover(
+        cover: Result<String, Error>,
+        path: &Path,
+        skipped: &mut Vec<ScanFailure>,
+    ) -> Option<String> {
+        cover
+            .map_err(|err| {
+                skipped.push(ScanFailure::new(
+                    path,
+                    format!("Cover art skipped: {}", err),
+                ))
+            })
+            .ok()
     }
 
     fn from_ffprobe(file_name: &Path) -> Result<Song, FfProbeError> {
         let probe = ffprobe::ffprobe(&file_name)?;
-        let mut title: String = "Unknown".to_owned();
-        let mut genres: Vec<String> = vec!["Unknown".to_owned()];
-        let mut artist: String = "Unknown".to_owned();
-        let mut album: String = "Single".to_owned();
-        let mut track: String = "1".to_owned();
+        let mut title: String = String::new();
+        let mut genres: Vec<String> = Vec::new();
+        let mut artist: String = String::new();
+        let mut album: String = String::new();
+        let mut track: String = String::new();
+        let mut album_artist: Option<String> = None;
+        let mut year: Option<u32> = None;
+        let mut disc: Option<u32> = None;
+        let mut composer: Option<String> = None;
         let path = file_name.to_owned();
 
+        let stream = probe
+            .streams
+            .iter()
+            .find(|stream| stream.codec_type.as_deref() == Some("audio"));
+        let duration = probe
+            .format
+            .duration
+            .as_ref()
+            .and_then(|duration| duration.parse::<f64>().ok())
+            .map(|duration| duration as u64);
+        let bitrate = probe
+            .format
+            .bit_rate
+            .as_ref()
+            .and_then(|bitrate| bitrate.parse::<u32>().ok())
+            .map(|bitrate| bitrate / 1000);
+        let codec = stream
+            .and_then(|stream| stream.codec_name.as_ref())
+            .map(|codec| codec.to_uppercase());
+        let sample_rate = stream
+            .and_then(|stream| stream.sample_rate.as_ref())
+            .and_then(|sample_rate| sample_rate.parse::<u32>().ok());
+
+        // Tag names differ between containers (ID3, Vorbis comments, MP4 atoms)
+        let sort_tag = |keys: &[&str]| {
+            probe.format.tags.as_ref().and_then(|tags| {
+                keys.iter()
+                    .find_map(|key| tags.extra.get(*key).and_then(|value| value.as_str()))
+                    .map(|name| name.to_owned())
+            })
+        };
+        let artist_sort = sort_tag(&["artist-sort", "artistsort", "sort_artist"]);
+        let album_artist_sort =
+            sort_tag(&["album_artist-sort", "albumartistsort", "sort_album_artist"]);
+        let album_sort = sort_tag(&["album-sort", "albumsort", "sort_album"]);
+
+        // Containers differ in the case of ReplayGain tags as well
+        let gain_tag = |key: &str| {
+            probe.format.tags.as_ref().and_then(|tags| {
+                tags.extra
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case(key))
+                    .and_then(|(_, value)| value.as_str())
+            })
+        };
+        let track_gain = gain_tag("replaygain_track_gain").and_then(gain::parse_gain);
+        let album_gain = gain_tag("replaygain_album_gain").and_then(gain::parse_gain);
+        let track_peak = gain_tag("replaygain_track_peak").and_then(gain::parse_peak);
+        let album_peak = gain_tag("replaygain_album_peak").and_then(gain::parse_peak);
+
         probe.format.tags.map(|tags| {
+            if let Some(album_artist_inner) =
+                tags.extra.get("album_artist").and_then(|v| v.as_str())
+            {
+                album_artist = Some(album_artist_inner.to_owned());
+            }
+            if let Some(date_inner) = tags.extra.get("date").and_then(|v| v.as_str()) {
+                year = date_inner.get(..4).and_then(|year| year.parse().ok());
+            }
+            if let Some(disc_inner) = tags.extra.get("disc").and_then(|v| v.as_str()) {
+                disc = disc_inner
+                    .split('/')
+                    .next()
+                    .and_then(|disc| disc.parse().ok());
+            }
+            if let Some(composer_inner) = tags.extra.get("composer").and_then(|v| v.as_str()) {
+                composer = Some(composer_inner.to_owned());
+            }
             tags.extra.get("title").map(|title_inner| {
                 title_inner.as_str().map(|title_inner| {
                     title = title_inner.to_owned();
@@ -262,7 +753,7 @@ impl Song {
         });
 
         return Ok(Song {
-            id: rand::random::<u64>(),
+            id: Self::id_from_path(file_name),
             title,
             genres,
             artist,
@@ -270,10 +761,49 @@ impl Song {
             track,
             path,
             cover: None,
+            album_artist,
+            year,
+            disc,
+            composer,
+            duration,
+            codec,
+            bitrate,
+            sample_rate,
+            artist_sort,
+            album_artist_sort,
+            album_sort,
+            track_gain,
+            album_gain,
+            track_peak,
+            album_peak,
+            ..Default::default()
         });
     }
 
-    pub fn play_single(&self) -> Result<Child, io::Error> {
+    /// Derived from the canonical path so that IDs (and cover names) survive rescans and restarts
+    fn id_from_path(path: &Path) -> u64 {
+        let path = fs::canonicalize(path).unwrap_or(path.to_owned());
+        utilities::hash_stable(path.to_string_lossy().as_bytes())
+    }
+
+    fn id_from_cue_track(path: &Path, track: u32) -> u64 {
+        let path = fs::canonicalize(path).unwrap_or(path.to_owned());
+        utilities::hash_stable(format!("{}#{}", path.to_string_lossy(), track).as_bytes())
+    }
+
+    /// Modification time (in seconds) and size of a file, used to skip re-reading unchanged songs
+    fn fingerprint(path: &Path) -> Result<(u64, u64), io::Error> {
+        let metadata = fs::metadata(path)?;
+        let modified = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        Ok((modified, metadata.len()))
+    }
+
+    pub fn play_single(&self, replay_gain: ReplayGainMode) -> Result<Child, io::Error> {
         Command::new("mpv")
             .arg("--no-video")
             .arg("--no-resume-playback")
@@ -281,12 +811,79 @@ impl Song {
             .arg("--no-terminal")
             .arg("--quiet")
             .arg(format!("{}{}", "--input-ipc-server=", MPV_SOCKET))
-            .arg(self.path.to_string_lossy().into_owned())
+            .arg(format!("--replaygain={}", replay_gain.mpv_value()))
+            .args(
+                self.fallback_gain(replay_gain)
+                    .map(|gain| format!("--replaygain-fallback={:.2}", gain)),
+            )
+            .arg(self.playable_path())
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .spawn()
     }
 
+    /// mpv reads ReplayGain tags itself, so this only matters for songs whose gain was measured by
+    /// `--analyze-gain` or comes from a cue sheet. See: https://mpv.io/manual/stable/#audio
+    fn fallback_gain(&self, replay_gain: ReplayGainMode) -> Option<f32> {
+        let (gain, peak) = match replay_gain {
+            ReplayGainMode::Off => return None,
+            ReplayGainMode::Track => (
+                self.track_gain.or(self.album_gain),
+                self.track_peak.or(self.album_peak),
+            ),
+            ReplayGainMode::Album => (
+                self.album_gain.or(self.track_gain),
+                self.album_peak.or(self.track_peak),
+            ),
+        };
+
+        gain.map(|gain| gain::clip_safe(gain, peak))
+    }
+
+    /// Parts of a file are played as an mpv EDL segment, so that mpv reports the position and duration
+    /// of the track rather than of the whole file. See: https://mpv.io/manual/stable/#edl
+    fn playable_path(&self) -> String {
+        let path = self.path.to_string_lossy().into_owned();
+        let Some(start) = self.start else {
+            return path;
+        };
+
+        let length = self
+            .end
+            .map(|end| format!(",{}", end.saturating_sub(start) as f64 / 1000.0))
+            .unwrap_or_default();
+        format!(
+            "edl://%{}%{},{}{}",
+            path.len(),
+            path,
+            start as f64 / 1000.0,
+            length
+        )
+    }
+
+    /// Codec, bitrate, and sample rate as far as they are known, e.g. "FLAC 1012 kbps 44.1 kHz"
+    pub fn format(&self) -> String {
+        let mut parts = Vec::new();
+
+        if let Some(codec) = &self.codec {
+            parts.push(codec.to_owned());
+        }
+
+        if let Some(bitrate) = self.bitrate {
+            parts.push(format!("{} kbps", bitrate));
+        }
+
+        if let Some(sample_rate) = self.sample_rate {
+            parts.push(format!("{} kHz", sample_rate as f64 / 1000.0));
+        }
+
+        if parts.is_empty() {
+            "Unknown".to_owned()
+        } else {
+            parts.join(" ")
+        }
+    }
+
     fn matches_query(&self, query: &String) -> bool {
         for query in query.split(",") {
             if query.starts_with("genre(") && query.ends_with(")") && query.len() > 8 {
@@ -309,105 +906,490 @@ impl Song {
                 if !self.album.to_lowercase().contains(&sub_query) {
                     return false;
                 }
-            } else if query.starts_with("!") {
-                if self.title.to_lowercase().contains(query)
-                    || self.artist.to_lowercase().contains(query)
+            } else if query.starts_with("year(") && query.ends_with(")") && query.len() > 7 {
+                let sub_query = query[5..query.len() - 1].to_owned();
+                if !self
+                    .year
+                    .is_some_and(|year| year.to_string().starts_with(&sub_query))
                 {
                     return false;
                 }
-            } else {
-                if !self.title.to_lowercase().contains(query)
-                    && !self.artist.to_lowercase().contains(query)
+            } else if query.starts_with("composer(") && query.ends_with(")") && query.len() > 11 {
+                let sub_query = query[9..query.len() - 1].to_owned();
+                if !self
+                    .composer
+                    .as_ref()
+                    .is_some_and(|composer| composer.to_lowercase().contains(&sub_query))
                 {
                     return false;
                 }
+            } else if query.starts_with("artist(") && query.ends_with(")") && query.len() > 9 {
+                let sub_query = query[7..query.len() - 1].to_owned();
+                if !self.matches_artist(&sub_query) {
+                    return false;
+                }
+            } else if let Some(sub_query) = query.strip_prefix("!") {
+                if self.title.to_lowercase().contains(sub_query) || self.matches_artist(sub_query) {
+                    return false;
+                }
+            } else {
+                if !self.title.to_lowercase().contains(query) && !self.matches_artist(query) {
+                    return false;
+                }
             }
         }
 
         return true;
     }
+
+    /// Matches the full artist credit as well as every individual artist in it
+    fn matches_artist(&self, query: &str) -> bool {
+        self.artist.to_lowercase().contains(query)
+            || self
+                .artists
+                .iter()
+                .any(|artist| artist.to_lowercase().contains(query))
+    }
+
+    pub fn artists_joined(&self) -> String {
+        if self.artists.is_empty() {
+            self.artist.to_owned()
+        } else {
+            self.artists.join(", ")
+        }
+    }
+
+    /// Sort name of the artist songs are grouped under, e.g. "Beatles, The"
+    fn sort_artist(&self) -> &str {
+        if self.album_artist.is_some() {
+            self.album_artist_sort
+                .as_deref()
+                .unwrap_or(self.primary_artist())
+        } else {
+            self.artist_sort.as_deref().unwrap_or(self.primary_artist())
+        }
+    }
+
+    fn sort_album(&self) -> &str {
+        self.album_sort.as_deref().unwrap_or(&self.album)
+    }
+
+    /// The artist songs are grouped under: the album artist if set, else the first credited artist
+    pub fn primary_artist(&self) -> &str {
+        self.album_artist
+            .as_deref()
+            .or(self.artists.first().map(|artist| artist.as_str()))
+            .unwrap_or(&self.artist)
+    }
 }
 
-impl Songs {
-    pub fn new(config: &Config, cache_path: &Path) -> Result<Songs, Error> {
-        let mut song_map = Vec::new();
-
-        if !config.is_manual_dir() && cache_path.exists() {
-            if let Ok(file) = File::open(cache_path) {
-                let songs_cached: Result<Vec<Song>, _> =
-                    serde_json::from_reader(BufReader::new(file));
-                if let Ok(song_cached) = songs_cached {
-                    song_map = song_cached;
-                }
+impl LibraryChanges {
+    pub fn new(changed: &[PathBuf]) -> LibraryChanges {
+        LibraryChanges {
+            paths: changed
+                .iter()
+                .map(|path| ScanRules::affected_path(path))
+                .collect(),
+            songs: Vec::new(),
+            failures: Vec::new(),
+        }
+    }
+
+    /// Reads the changed files, reusing the songs in `previous` whose files did not change
+    pub fn probe(&mut self, config: &Config, previous: Vec<Song>) {
+        let rules = ScanRules::new(config);
+        let roots = config.music_directories();
+        let previous: HashMap<u64, Song> =
+            previous.into_iter().map(|song| (song.id, song)).collect();
+
+        for path in self.paths.iter().filter(|path| path.exists()) {
+            if let Some(root) = roots.iter().find(|root| path.starts_with(root)) {
+                let mut song_paths = Vec::new();
+                rules.collect(root, path, &mut song_paths, &mut self.failures);
+
+                let (songs, failures) =
+                    Songs::probe_songs(root, &song_paths, &previous, Some(utilities::now()), None);
+                self.songs.extend(songs);
+                self.failures.extend(failures);
             }
         }
+    }
+
+    fn affects(&self, song: &Song) -> bool {
+        self.paths.iter().any(|path| {
+            song.path.starts_with(path)
+                || song.cue.as_ref().is_some_and(|cue| cue.starts_with(path))
+        })
+    }
+}
 
-        let mut songs = Songs {
-            songs_data_library: song_map,
+impl Songs {
+    pub fn new(config: &Config, cache_path: &Path) -> Result<Songs, Error> {
+        let mut songs = Self::empty()?;
+        let previous = Self::read_cache(config, cache_path)?;
+        songs.rescan(config, previous, cache_path)?;
+        Ok(songs)
+    }
+
+    /// A library without any songs yet, to be filled in by a scan
+    pub fn empty() -> Result<Songs, Error> {
+        Ok(Songs {
+            songs_data_library: Vec::new(),
             showing_songs_library: SongList::All,
             songs_next: Vec::new(),
             songs_history: Vec::new(),
             active: ActiveSong::new(),
-        };
+            report: ScanReport::default(),
+            hidden: Self::read_hidden()?,
+            scan_positions: None,
+        })
+    }
 
-        if !songs.songs_data_library.is_empty() {
-            return Ok(songs);
+    /// The songs of the last scan, which are only read again if their files changed
+    pub fn read_cache(config: &Config, cache_path: &Path) -> Result<Vec<Song>, Error> {
+        if config.is_manual_dir() {
+            return Ok(Vec::new());
         }
 
-        match songs.load_songs(config) {
-            Err(errors) => Err(errors),
-            Ok(loaded) => {
-                songs.songs_data_library = loaded;
-                Self::sort(&mut songs.songs_data_library);
+        // After switching cache formats, the cache in the other format is still worth reusing
+        let fallback_path = files::cache_path(!config.binary_cache)?;
+        Ok(LibraryCache::read(cache_path)
+            .or_else(|_| LibraryCache::read(&fallback_path))
+            .map(|cache| cache.songs)
+            .unwrap_or_default())
+    }
 
-                if !config.is_manual_dir() {
-                    if let Ok(json) = serde_json::to_string(&songs.songs_data_library) {
-                        if let Ok(mut cache_file) = File::create(cache_path) {
-                            cache_file.write_all(json.as_bytes()).unwrap();
-                        }
-                    }
+    /// Scans every library root, only re-reading files that are new or changed since `previous`. Every
+    /// root is walked before any file is read, so that `progress` knows the total early on
+    pub fn load_songs(
+        config: &Config,
+        previous: Vec<Song>,
+        progress: Option<&ScanProgress>,
+    ) -> (Vec<Song>, ScanReport) {
+        let rules = ScanRules::new(config).with_progress(progress);
+        let previous_covers = Self::covers(&previous);
+        let previous: HashMap<u64, Song> =
+            previous.into_iter().map(|song| (song.id, song)).collect();
+
+        // Without any previous library, the modification time is the best guess for when songs were added
+        let added = (!previous.is_empty()).then(utilities::now);
+        let mut songs = Vec::new();
+        let mut failures = Vec::new();
+        let mut root_paths = Vec::new();
+
+        for root in config.music_directories() {
+            // An unmounted drive looks like an empty directory, so its cached songs are kept
+            if !root
+                .read_dir()
+                .is_ok_and(|mut entries| entries.next().is_some())
+            {
+                failures.push(ScanFailure::new(
+                    &root,
+                    "Library root is unavailable, showing its cached songs",
+                ));
+                songs.extend(
+                    previous
+                        .values()
+                        .filter(|song| song.path.starts_with(&root))
+                        .cloned(),
+                );
+                continue;
+            }
+
+            let mut song_paths = Vec::new();
+            rules.collect(&root, &root, &mut song_paths, &mut failures);
+            root_paths.push((root, song_paths));
+        }
+
+        if let Some(progress) = progress {
+            progress.add_probed(&[], 0, failures.len());
+            progress.start_probing();
+        }
+
+        for (root, song_paths) in root_paths {
+            let (root_songs, root_failures) =
+                Self::probe_songs(&root, &song_paths, &previous, added, progress);
+            songs.extend(root_songs);
+            failures.extend(root_failures);
+        }
+
+        Self::remove_unused_covers(previous_covers, &songs);
+        (songs, ScanReport::new(failures))
+    }
+
+    /// New songs are marked as added at `added`, or at their modification time if it is `None`
+    fn probe_songs(
+        root: &Path,
+        song_paths: &[PathBuf],
+        previous: &HashMap<u64, Song>,
+        added: Option<u64>,
+        progress: Option<&ScanProgress>,
+    ) -> (Vec<Song>, Vec<ScanFailure>) {
+        rlimit::increase_nofile_limit(u64::MAX).unwrap();
+
+        let (cue_paths, song_paths): (Vec<&PathBuf>, Vec<&PathBuf>) =
+            song_paths.iter().partition(|path| CueSheet::is_cue(path));
+        let cue_count = cue_paths.len();
+
+        // Files split by a cue sheet are only listed as its tracks, even if several sheets describe them.
+        // A file holding a single track (like a per-track rip) stays an ordinary song
+        let mut failures = Vec::new();
+        let mut sheets = Vec::new();
+        let mut split_files = HashSet::new();
+        for cue_path in cue_paths {
+            match CueSheet::read(cue_path) {
+                Ok(mut sheet) => {
+                    sheet.files.retain(|file| {
+                        file.tracks.len() > 1 && split_files.insert(file.path.clone())
+                    });
+                    sheets.push((cue_path, sheet));
                 }
+                Err(err) => failures.push(ScanFailure::new(cue_path, err)),
+            }
+        }
+
+        // Cue sheets and the files they split count as probed once their tracks are reported
+        if let Some(progress) = progress {
+            let split = song_paths
+                .iter()
+                .filter(|path| split_files.contains(**path))
+                .count();
+            progress.add_probed(&[], split + cue_count, failures.len());
+        }
 
-                Ok(songs)
+        // Songs taken unchanged from `previous` are already shown, so only songs read again are passed on
+        let report = |result: &Result<Song, ScanFailure>, files: usize| {
+            if let Some(progress) = progress {
+                match result {
+                    Ok(song)
+                        if previous.get(&song.id).is_some_and(|cached| {
+                            cached.modified == song.modified && cached.size == song.size
+                        }) =>
+                    {
+                        progress.add_probed(&[], files, 0)
+                    }
+                    Ok(song) => progress.add_probed(std::slice::from_ref(song), files, 0),
+                    Err(_) => progress.add_probed(&[], files, 1),
+                }
             }
+        };
+
+        let results: Vec<Result<Song, ScanFailure>> = song_paths
+            .par_iter()
+            .filter(|path| !split_files.contains(**path))
+            .flat_map_iter(|path| {
+                let mut skipped = Vec::new();
+                let result = Self::probe_song(root, path, previous, added, &mut skipped);
+                report(&result, 1);
+                iter::once(result).chain(skipped.into_iter().map(Err))
+            })
+            .chain(sheets.par_iter().flat_map_iter(|(cue_path, sheet)| {
+                sheet.files.iter().flat_map(|file| {
+                    let mut skipped = Vec::new();
+                    let results: Vec<Result<Song, ScanFailure>> = match Self::probe_cue_file(
+                        root,
+                        cue_path,
+                        sheet,
+                        file,
+                        previous,
+                        added,
+                        &mut skipped,
+                    ) {
+                        Ok(songs) => songs.into_iter().map(Ok).collect(),
+                        Err(err) => vec![Err(err)],
+                    };
+                    results.iter().for_each(|result| report(result, 0));
+                    results.into_iter().chain(skipped.into_iter().map(Err))
+                })
+            }))
+            .collect();
+
+        let (songs, root_failures): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+        let songs: Vec<Song> = songs.into_iter().filter_map(Result::ok).collect();
+        failures.extend(root_failures.into_iter().filter_map(Result::err));
+
+        // Every song in a folder shares its sidecar cover, so an unreadable one is only listed once
+        let mut listed = HashSet::new();
+        failures.retain(|failure| listed.insert((failure.path.clone(), failure.reason.clone())));
+
+        (songs, failures)
+    }
+
+    fn probe_song(
+        root: &Path,
+        path: &Path,
+        previous: &HashMap<u64, Song>,
+        added: Option<u64>,
+        skipped: &mut Vec<ScanFailure>,
+    ) -> Result<Song, ScanFailure> {
+        let (modified, size) =
+            Song::fingerprint(path).map_err(|err| ScanFailure::new(path, err))?;
+
+        // Entries cached with an outdated ID are not found, so they are read again to migrate them
+        let cached = previous.get(&Song::id_from_path(path));
+        let added = Self::added_at(cached, added, modified);
+
+        if let Some(song) = cached.filter(|song| song.modified == modified && song.size == size) {
+            return Ok(Song {
+                root: root.to_owned(),
+                added,
+                ..song.clone()
+            });
         }
+
+        Song::new(path, skipped)
+            .map(|song| Song {
+                missing: TagFields::missing(&song),
+                root: root.to_owned(),
+                modified,
+                size,
+                added,
+                ..song
+            })
+            .map_err(|err| ScanFailure::new(path, err))
     }
 
-    pub fn load_songs(&mut self, config: &Config) -> Result<Vec<Song>, Error> {
-        let mut song_paths = Vec::new();
-        files::art_path_delete()?;
-        Self::load_dir(&config.music_directory(), &mut song_paths);
+    /// Every track of a cue sheet becomes a song that plays part of the file, with the file's own
+    /// tags filling in whatever the cue sheet leaves out
+    fn probe_cue_file(
+        root: &Path,
+        cue_path: &Path,
+        sheet: &CueSheet,
+        file: &CueFile,
+        previous: &HashMap<u64, Song>,
+        added: Option<u64>,
+        skipped: &mut Vec<ScanFailure>,
+    ) -> Result<Vec<Song>, ScanFailure> {
+        let (cue_modified, cue_size) =
+            Song::fingerprint(cue_path).map_err(|err| ScanFailure::new(cue_path, err))?;
+        let (file_modified, file_size) =
+            Song::fingerprint(&file.path).map_err(|err| ScanFailure::new(&file.path, err))?;
+
+        // Editing either the cue sheet or the file changes the tracks
+        let modified = cue_modified.max(file_modified);
+        let size = cue_size + file_size;
+
+        let ids: Vec<u64> = file
+            .tracks
+            .iter()
+            .map(|track| Song::id_from_cue_track(&file.path, track.number))
+            .collect();
+        let cached: Vec<&Song> = ids
+            .iter()
+            .filter_map(|id| previous.get(id))
+            .filter(|song| song.modified == modified && song.size == size)
+            .collect();
 
-        rlimit::increase_nofile_limit(u64::MAX).unwrap();
+        if cached.len() == ids.len() {
+            return Ok(cached
+                .into_iter()
+                .map(|song| Song {
+                    root: root.to_owned(),
+                    added: Self::added_at(Some(song), added, modified),
+                    ..song.clone()
+                })
+                .collect());
+        }
 
-        let results: Vec<Result<Song, Error>> =
-            song_paths.par_iter().map(|path| Song::new(path)).collect();
+        let song =
+            Song::new(&file.path, skipped).map_err(|err| ScanFailure::new(&file.path, err))?;
+        let length = song.duration.map(|duration| duration * 1000);
 
-        let valid: Vec<&Song> = results
+        Ok(file
+            .tracks
             .iter()
-            .filter_map(|song| song.as_ref().ok())
-            .collect();
+            .zip(ids)
+            .map(|(track, id)| Song {
+                id,
+                title: track
+                    .title
+                    .clone()
+                    .unwrap_or(format!("Track {}", track.number)),
+                artist: track
+                    .performer
+                    .clone()
+                    .or(sheet.performer.clone())
+                    .unwrap_or(song.artist.clone()),
+                album: sheet.title.clone().unwrap_or(song.album.clone()),
+                track: track.number.to_string(),
+                genres: sheet
+                    .genre
+                    .clone()
+                    .map(|genre| vec![genre])
+                    .unwrap_or(song.genres.clone()),
+                album_artist: song.album_artist.clone().or(sheet.performer.clone()),
+                year: sheet.year.or(song.year),
+                composer: track
+                    .songwriter
+                    .clone()
+                    .or(sheet.songwriter.clone())
+                    .or(song.composer.clone()),
+                duration: track
+                    .end
+                    .or(length)
+                    .map(|end| end.saturating_sub(track.start) / 1000),
+                start: Some(track.start),
+                end: track.end,
+                cue: Some(cue_path.to_owned()),
+                artist_sort: None,
+                // The gain of the whole file is the gain of the album it holds
+                track_gain: track.gain,
+                track_peak: track.peak,
+                album_gain: sheet.album_gain.or(song.album_gain).or(song.track_gain),
+                album_peak: sheet.album_peak.or(song.album_peak).or(song.track_peak),
+                root: root.to_owned(),
+                modified,
+                size,
+                added: Self::added_at(previous.get(&id), added, modified),
+                ..song.clone()
+            })
+            .map(|song| Song {
+                missing: TagFields::missing(&song),
+                ..song
+            })
+            .collect())
+    }
 
-        if valid.len() == results.len() {
-            Ok(results.into_iter().filter_map(|song| song.ok()).collect())
-        } else {
-            Err(Error::msg(
-                results
-                    .into_iter()
-                    .filter_map(|result| result.err().map(|err| err.to_string()))
-                    .reduce(|acc, e| format!("{}\n{}", acc, e))
-                    .unwrap_or("Music Error".to_owned()),
-            ))
+    /// Songs cached before `added` existed fall back to their modification time
+    fn added_at(cached: Option<&Song>, added: Option<u64>, modified: u64) -> u64 {
+        match cached {
+            Some(song) if song.added > 0 => song.added,
+            Some(_) => modified,
+            None => added.unwrap_or(modified),
         }
     }
 
-    pub fn showing_songs_library(&self) -> Vec<&Song> {
+    fn covers(songs: &[Song]) -> HashSet<String> {
+        songs.iter().filter_map(|song| song.cover.clone()).collect()
+    }
+
+    /// Covers of removed or re-read songs are no longer referenced
+    fn remove_unused_covers(previous_covers: HashSet<String>, songs: &[Song]) {
+        let covers = Self::covers(songs);
+        previous_covers
+            .iter()
+            .filter(|cover| !covers.contains(*cover))
+            .for_each(|cover| {
+                let _ = fs::remove_file(cover);
+            });
+    }
+
+    /// Iterated rather than collected, since only the visible rows are needed for every frame
+    pub fn showing_songs_library(&self) -> Box<dyn Iterator<Item = &Song> + '_> {
         match &self.showing_songs_library {
-            SongList::All => self.songs_data_library.iter().collect(),
-            SongList::Filtered(indicies) => indicies
-                .iter()
-                .map(|index| self.songs_data_library.get(*index).unwrap())
-                .collect::<Vec<&Song>>(),
+            SongList::All => Box::new(self.songs_data_library.iter()),
+            SongList::Filtered(indicies) => Box::new(
+                indicies
+                    .iter()
+                    .map(|index| self.songs_data_library.get(*index).unwrap()),
+            ),
+        }
+    }
+
+    pub fn songs_showing_library(&self) -> usize {
+        match &self.showing_songs_library {
+            SongList::All => self.songs_data_library.len(),
+            SongList::Filtered(indicies) => indicies.len(),
         }
     }
 
@@ -421,6 +1403,22 @@ impl Songs {
             .flatten()
     }
 
+    pub fn song(&self, index: usize) -> Option<&Song> {
+        self.songs_data_library.get(index)
+    }
+
+    pub fn song_by_id(&self, id: u64) -> Option<&Song> {
+        self.songs_data_library.iter().find(|song| song.id == id)
+    }
+
+    /// The current song followed by everything in "Up Next"
+    pub fn queue(&self) -> Vec<&Song> {
+        self.songs_next
+            .iter()
+            .filter_map(|index| self.songs_data_library.get(*index))
+            .collect()
+    }
+
     pub fn next_playing(&self) -> Vec<&Song> {
         if self.songs_next.len() <= 1 {
             Vec::new()
@@ -482,8 +1480,12 @@ impl Songs {
         self.songs_next.insert(1, selected);
     }
 
-    pub fn try_play_current_song(&mut self, load_cover: bool) -> Result<(), io::Error> {
-        self.active = ActiveSong::with_song(self.current_song(), load_cover)?;
+    pub fn try_play_current_song(
+        &mut self,
+        load_cover: bool,
+        replay_gain: ReplayGainMode,
+    ) -> Result<(), io::Error> {
+        self.active = ActiveSong::with_song(self.current_song(), load_cover, replay_gain)?;
         Ok(())
     }
 
@@ -522,12 +1524,88 @@ impl Songs {
         self.songs_next.get(selected).copied()
     }
 
+    /// Queues the songs at the given paths in order, returning the paths that are not in the library
+    pub fn push_back_paths(&mut self, paths: &[PathBuf]) -> Vec<PathBuf> {
+        let positions = self.positions_by_id();
+
+        let mut unmatched = Vec::new();
+        for path in paths {
+            // IDs come from canonical paths, so this matches however the path was written
+            match positions.get(&Song::id_from_path(path)) {
+                Some(position) => self.songs_next.push(*position),
+                None => unmatched.push(path.to_owned()),
+            }
+        }
+
+        unmatched
+    }
+
+    /// Looks up the library songs at the given paths, leaving out paths that are not in the library
+    pub fn songs_at_paths(&self, paths: &[PathBuf]) -> Vec<&Song> {
+        let positions = self.positions_by_id();
+        paths
+            .iter()
+            .filter_map(|path| positions.get(&Song::id_from_path(path)))
+            .filter_map(|position| self.songs_data_library.get(*position))
+            .collect()
+    }
+
+    fn positions_by_id(&self) -> HashMap<u64, usize> {
+        self.songs_data_library
+            .iter()
+            .enumerate()
+            .map(|(i, song)| (song.id, i))
+            .collect()
+    }
+
+    pub fn is_hidden(&self, song: &Song) -> bool {
+        self.hidden.contains(&song.id)
+    }
+
+    /// Hides or shows the songs at the given library indices, saving the change
+    pub fn set_hidden(&mut self, indices: &[usize], hidden: bool) -> Result<(), Error> {
+        for song in indices
+            .iter()
+            .filter_map(|index| self.songs_data_library.get(*index))
+        {
+            if hidden {
+                self.hidden.insert(song.id);
+            } else {
+                self.hidden.remove(&song.id);
+            }
+        }
+
+        let mut ids: Vec<&u64> = self.hidden.iter().collect();
+        ids.sort();
+        let json = serde_json::to_string(&ids).map_err(|err| Error::new(err))?;
+        fs::write(files::hidden_path()?, json).map_err(|err| Error::new(err))
+    }
+
+    fn read_hidden() -> Result<HashSet<u64>, Error> {
+        match fs::read_to_string(files::hidden_path()?) {
+            Ok(json) => serde_json::from_str(&json).map_err(|err| {
+                Error::msg(format!("Could not read the hidden duplicates: {}", err))
+            }),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(HashSet::new()),
+            Err(err) => Err(Error::new(err)),
+        }
+    }
+
+    pub fn find_duplicates(&self, hashes: Option<&ContentHashes>) -> Vec<Vec<usize>> {
+        duplicates::find(&self.songs_data_library, hashes)
+    }
+
+    pub fn missing_content_hashes(&self, hashes: &ContentHashes) -> Vec<HashedFile> {
+        hashes.missing(&self.songs_data_library)
+    }
+
     pub fn push_back_all(&mut self) {
         let mut adding: Vec<usize> = match &self.showing_songs_library {
             SongList::All => (0..self.songs_in_library() - 1).collect(),
             SongList::Filtered(songs) => songs.clone(),
         };
 
+        adding.retain(|song| !self.is_hidden(&self.songs_data_library[*song]));
         adding.shuffle(&mut rand::thread_rng());
         adding.iter().for_each(|song| {
             self.songs_next.push(*song);
@@ -536,45 +1614,308 @@ impl Songs {
 
     pub fn reload(&mut self, config: &Config) -> Result<(), Error> {
         self.showing_songs_library.clear();
-        self.songs_data_library.clear();
         self.songs_next.clear();
         self.kill_current();
 
-        match self.load_songs(config) {
-            Ok(loaded) => {
-                self.songs_data_library = loaded;
-                Self::sort(&mut self.songs_data_library);
+        let previous = std::mem::take(&mut self.songs_data_library);
+        self.rescan(config, previous, &files::cache_path(config.binary_cache)?)
+    }
+
+    /// Songs affected by the changes, whose cached copies spare reading the files that did not change
+    pub fn changed_songs(&self, changes: &LibraryChanges) -> Vec<Song> {
+        self.songs_data_library
+            .iter()
+            .filter(|song| changes.affects(song))
+            .cloned()
+            .collect()
+    }
 
-                if !config.is_manual_dir() {
-                    if let Ok(json) = serde_json::to_string(&self.songs_data_library) {
-                        if let Ok(mut cache_file) = File::create(files::cache_path()?) {
-                            cache_file.write_all(json.as_bytes()).unwrap();
-                        }
-                    }
+    /// Swaps the songs read by `changes` in for the ones they affect
+    pub fn apply_changes(&mut self, config: &Config, changes: LibraryChanges) -> Result<(), Error> {
+        // The library may have changed while the files were read, so what is stale is decided again
+        let (stale, mut library): (Vec<Song>, Vec<Song>) = self
+            .songs_data_library
+            .iter()
+            .cloned()
+            .partition(|song| changes.affects(song));
+
+        let previous_covers = Self::covers(&stale);
+        library.extend(changes.songs);
+
+        Self::remove_unused_covers(previous_covers, &library);
+        Self::derive_fields(config, &mut library);
+        self.replace_library(config, library);
+        self.report.merge(&changes.paths, changes.failures);
+        self.report.write_log(&files::scan_log_path()?)?;
+        self.write_cache(config, &files::cache_path(config.binary_cache)?)
+    }
+
+    fn rescan(
+        &mut self,
+        config: &Config,
+        previous: Vec<Song>,
+        cache_path: &Path,
+    ) -> Result<(), Error> {
+        let (songs, report) = Self::load_songs(config, previous, None);
+        self.finish_scan(config, songs, report, cache_path)
+    }
+
+    /// Shows the songs of the last scan while the library is scanned again
+    pub fn seed(&mut self, config: &Config, mut songs: Vec<Song>) {
+        Self::derive_fields(config, &mut songs);
+        self.replace_library(config, songs);
+    }
+
+    /// Merges songs read so far by a background scan into the library. Changed songs are updated in
+    /// place and new songs are added at the end, so the library is only sorted once the scan is done
+    pub fn add_partial(&mut self, config: &Config, mut songs: Vec<Song>) {
+        Self::derive_fields(config, &mut songs);
+
+        let mut positions = self
+            .scan_positions
+            .take()
+            .unwrap_or_else(|| self.positions_by_id());
+        for song in songs {
+            match positions.get(&song.id) {
+                Some(position) => self.songs_data_library[*position] = song,
+                None => {
+                    positions.insert(song.id, self.songs_data_library.len());
+                    self.songs_data_library.push(song);
                 }
+            }
+        }
+
+        self.scan_positions = Some(positions);
+    }
 
-                Ok(())
+    /// Replaces the partial results of a scan with the whole library
+    pub fn finish_scan(
+        &mut self,
+        config: &Config,
+        mut songs: Vec<Song>,
+        report: ScanReport,
+        cache_path: &Path,
+    ) -> Result<(), Error> {
+        Self::derive_fields(config, &mut songs);
+        self.replace_library(config, songs);
+        self.report = report;
+        self.report.write_log(&files::scan_log_path()?)?;
+        self.write_cache(config, cache_path)
+    }
+
+    /// Reads a single song again after its tags were edited, without rescanning its folder
+    pub fn reload_song(&mut self, config: &Config, id: u64) -> Result<(), Error> {
+        let Some(index) = self
+            .songs_data_library
+            .iter()
+            .position(|song| song.id == id)
+        else {
+            return Ok(());
+        };
+        let song = &self.songs_data_library[index];
+
+        // Without a cached copy the file is always read again, while keeping when it was added
+        let (mut songs, failures) = Self::probe_songs(
+            &song.root,
+            std::slice::from_ref(&song.path),
+            &HashMap::new(),
+            Some(song.added),
+            None,
+        );
+        // Failures are only fatal without a song, as a cover that cannot be read is skipped
+        let Some(reloaded) = songs.pop() else {
+            return match failures.into_iter().next() {
+                Some(failure) => Err(Error::msg(failure.reason)),
+                None => Ok(()),
+            };
+        };
+
+        let mut library = self.songs_data_library.clone();
+        library[index] = reloaded;
+
+        Self::derive_fields(config, &mut library);
+        self.replace_library(config, library);
+        self.write_cache(config, &files::cache_path(config.binary_cache)?)
+    }
+
+    /// Measures songs without ReplayGain tags with ffmpeg, then derives album gains from the tracks of
+    /// each album. The results are kept in the cache. Returns the number of songs measured and the
+    /// songs that could not be
+    pub fn analyze_gain(
+        &mut self,
+        config: &Config,
+        cache_path: &Path,
+        progress: impl Fn(usize, usize) + Sync,
+    ) -> Result<(usize, Vec<ScanFailure>), Error> {
+        let pending: Vec<usize> = (0..self.songs_data_library.len())
+            .filter(|i| self.songs_data_library[*i].track_gain.is_none())
+            .collect();
+
+        let done = AtomicUsize::new(0);
+        let results: Vec<(usize, Result<gain::Loudness, Error>)> = pending
+            .par_iter()
+            .map(|i| {
+                let song = &self.songs_data_library[*i];
+                let loudness = gain::analyze(&song.path, song.start, song.end);
+                progress(done.fetch_add(1, Ordering::Relaxed) + 1, pending.len());
+                (*i, loudness)
+            })
+            .collect();
+
+        let mut measured = 0;
+        let mut failures = Vec::new();
+        for (i, loudness) in results {
+            let song = &mut self.songs_data_library[i];
+            match loudness {
+                Ok(loudness) => {
+                    song.track_gain = Some(gain::gain_from_loudness(loudness.integrated));
+                    song.track_peak = Some(loudness.peak);
+                    measured += 1;
+                }
+                Err(err) => failures.push(ScanFailure::new(&song.path, err)),
             }
-            Err(errors) => Err(errors),
         }
+
+        // Albums are told apart by their folder too, since many share names like "Greatest Hits"
+        let mut albums: HashMap<(&Path, &str), Vec<usize>> = HashMap::new();
+        for (i, song) in self.songs_data_library.iter().enumerate() {
+            let dir = song.path.parent().unwrap_or(Path::new(""));
+            albums.entry((dir, &song.album)).or_default().push(i);
+        }
+
+        let album_gains: Vec<(Vec<usize>, f32, Option<f32>)> = albums
+            .into_values()
+            .filter_map(|tracks| {
+                let songs: Vec<&Song> = tracks
+                    .iter()
+                    .map(|i| &self.songs_data_library[*i])
+                    .collect();
+                if songs.iter().all(|song| song.album_gain.is_some()) {
+                    return None;
+                }
+
+                let loudness: Option<Vec<(f32, u64)>> = songs
+                    .iter()
+                    .map(|song| {
+                        song.track_gain.map(|track_gain| {
+                            (
+                                gain::loudness_from_gain(track_gain),
+                                song.duration.unwrap_or(1),
+                            )
+                        })
+                    })
+                    .collect();
+                let album_loudness = gain::album_loudness(&loudness?)?;
+                let album_peak = songs
+                    .iter()
+                    .filter_map(|song| song.track_peak)
+                    .reduce(f32::max);
+
+                Some((tracks, gain::gain_from_loudness(album_loudness), album_peak))
+            })
+            .collect();
+
+        for (tracks, album_gain, album_peak) in album_gains {
+            for i in tracks {
+                let song = &mut self.songs_data_library[i];
+                if song.album_gain.is_none() {
+                    song.album_gain = Some(album_gain);
+                    song.album_peak = album_peak;
+                }
+            }
+        }
+
+        self.write_cache(config, cache_path)?;
+        Ok((measured, failures))
+    }
+
+    /// Re-applies the path templates, artist separators, aliases, and sort order, which may have changed with the config
+    pub fn apply_config(&mut self, config: &Config) {
+        // Cloned rather than taken, since the queue is matched against the current library
+        let mut library = self.songs_data_library.clone();
+        Self::derive_fields(config, &mut library);
+        self.replace_library(config, library);
+    }
+
+    /// Fills in what depends on the config: fields missing from the tags, then the credited artists
+    fn derive_fields(config: &Config, songs: &mut [Song]) {
+        let templates = PathTemplates::new(config);
+        let rules = ArtistRules::new(config);
+        songs.iter_mut().for_each(|song| {
+            templates.apply(song);
+            song.artists = rules.split(&song.artist);
+        });
+    }
+
+    /// Swaps in a new library while keeping the queue and history pointing at the same songs
+    fn replace_library(&mut self, config: &Config, mut library: Vec<Song>) {
+        Self::sort(&mut library, config.sort_order);
+
+        let ids = |indices: &Vec<usize>| -> Vec<u64> {
+            indices
+                .iter()
+                .filter_map(|index| self.songs_data_library.get(*index))
+                .map(|song| song.id)
+                .collect()
+        };
+        let next_ids = ids(&self.songs_next);
+        let history_ids = ids(&self.songs_history);
+
+        let positions: HashMap<u64, usize> = library
+            .iter()
+            .enumerate()
+            .map(|(i, song)| (song.id, i))
+            .collect();
+
+        // The playing file is gone, so stop it without skipping the song queued after it
+        if next_ids
+            .first()
+            .is_some_and(|current| !positions.contains_key(current))
+        {
+            self.kill_current();
+            self.active = ActiveSong::new();
+        }
+
+        self.songs_next = next_ids
+            .iter()
+            .filter_map(|id| positions.get(id).copied())
+            .collect();
+        self.songs_history = history_ids
+            .iter()
+            .filter_map(|id| positions.get(id).copied())
+            .collect();
+        self.songs_data_library = library;
+        self.scan_positions = None;
+        self.showing_songs_library = SongList::All;
+    }
+
+    fn write_cache(&self, config: &Config, cache_path: &Path) -> Result<(), Error> {
+        if config.is_manual_dir() {
+            return Ok(());
+        }
+
+        LibraryCache::new(config.music_directories(), self.songs_data_library.clone())
+            .write(cache_path)
+            .map_err(|err| err.wrap_err("Could not write library cache"))
     }
 
     pub fn filter_apply(&mut self, query: Option<&String>) {
         let query = query.map(|query| query.to_lowercase());
 
+        // Matching is independent per song, which matters for very large libraries
         let filtered: Vec<usize> = if query
             .as_ref()
             .map(|query| query.is_empty())
             .unwrap_or(false)
         {
-            if self.songs_data_library.len() > 0 {
+            if !self.songs_data_library.is_empty() {
                 (0..self.songs_data_library.len() - 1).collect()
             } else {
                 Vec::new()
             }
         } else {
             self.songs_data_library
-                .iter()
+                .par_iter()
                 .enumerate()
                 .filter(|(_, song)| match &query {
                     Some(query) => song.matches_query(query),
@@ -587,35 +1928,72 @@ impl Songs {
         self.showing_songs_library = SongList::Filtered(filtered)
     }
 
+    /// Evaluates a smart playlist against the current library
+    pub fn smart_songs(&self, smart: &SmartQuery) -> Vec<&Song> {
+        let query = smart.query.to_lowercase();
+        let mut songs: Vec<&Song> = self
+            .songs_data_library
+            .par_iter()
+            .filter(|song| song.matches_query(&query))
+            .collect();
+
+        match smart.order {
+            SmartOrder::Library => {}
+            SmartOrder::Random => {
+                songs.retain(|song| !self.is_hidden(song));
+                songs.shuffle(&mut rand::thread_rng());
+            }
+            SmartOrder::Artist => Self::sort(&mut songs, SortOrder::Artist),
+            SmartOrder::Title => Self::sort(&mut songs, SortOrder::Title),
+            SmartOrder::Added => Self::sort(&mut songs, SortOrder::Added),
+            SmartOrder::Year => Self::sort(&mut songs, SortOrder::Year),
+        }
+
+        if let Some(limit) = smart.limit {
+            songs.truncate(limit);
+        }
+
+        songs
+    }
+
     pub fn unfiltered_apply(&mut self) {
         self.showing_songs_library = SongList::All;
     }
 
-    fn load_dir(dir: &Path, song_paths: &mut Vec<PathBuf>) {
-        if let Ok(child) = dir.read_dir() {
-            child.for_each(|child_result| {
-                let Ok(child) = child_result else {
-                    return;
-                };
+    fn sort<S: Borrow<Song>>(songs: &mut [S], order: SortOrder) {
+        // Tracks are compared as numbers so that "10" comes after "2"
+        let by_album = |first: &Song, second: &Song| {
+            utilities::cmp_ignore_case(first.sort_artist(), second.sort_artist())
+                .then_with(|| utilities::cmp_ignore_case(first.sort_album(), second.sort_album()))
+                .then(first.disc.cmp(&second.disc))
+                .then(
+                    first
+                        .track
+                        .parse::<u32>()
+                        .ok()
+                        .cmp(&second.track.parse::<u32>().ok()),
+                )
+                .then_with(|| first.track.cmp(&second.track))
+                .then_with(|| utilities::cmp_ignore_case(&first.title, &second.title))
+        };
 
-                let child_path = child.path();
-                if child_path.is_dir() {
-                    Self::load_dir(&child_path, song_paths);
-                } else {
-                    song_paths.push(child_path);
-                }
-            });
-        }
-    }
+        let compare = |first: &Song, second: &Song| match order {
+            SortOrder::Artist => by_album(first, second),
+            SortOrder::Title => utilities::cmp_ignore_case(&first.title, &second.title)
+                .then_with(|| by_album(first, second)),
+            SortOrder::Added => second
+                .added
+                .cmp(&first.added)
+                .then_with(|| by_album(first, second)),
+            // Songs without a known year go last
+            SortOrder::Year => first
+                .year
+                .is_none()
+                .cmp(&second.year.is_none())
+                .then(first.year.cmp(&second.year))
+                .then_with(|| by_album(first, second)),
+        };
 
-    fn sort(songs: &mut Vec<Song>) {
-        songs.sort_by(|first, second| {
-            first
-                .artist
-                .cmp(&second.artist)
-                .then(first.album.cmp(&second.album))
-                .then(first.track.cmp(&second.track))
-                .then(first.title.cmp(&second.title))
-        });
+        songs.sort_by(|first, second| compare(first.borrow(), second.borrow()));
     }
 }
@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use color_eyre::eyre::Error;
+use id3::{Tag, TagLike, Version};
+use lofty::{
+    config::WriteOptions,
+    file::{AudioFile, TaggedFileExt},
+    tag::{Accessor, ItemKey, ItemValue, TagItem},
+};
+
+/// Tags changed in the tag editor, to be written back to the file of a song
+pub struct TagEdit {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub genres: Vec<String>,
+    pub track: Option<u32>,
+}
+
+impl TagEdit {
+    /// MP3s are written with the `id3` crate, which is also what reads them, and everything else with
+    /// lofty. Empty fields remove the tag
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let is_mp3 = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("mp3"));
+
+        if is_mp3 {
+            self.write_id3(path)
+        } else {
+            self.write_native(path)
+        }
+    }
+
+    fn write_id3(&self, path: &Path) -> Result<(), Error> {
+        let mut tag = match Tag::read_from_path(path) {
+            Ok(tag) => tag,
+            Err(err) if matches!(err.kind, id3::ErrorKind::NoTag) => Tag::new(),
+            Err(err) => match err.partial_tag {
+                Some(tag) => tag,
+                None => return Err(Error::msg(err.description)),
+            },
+        };
+
+        match self.title.as_str() {
+            "" => tag.remove_title(),
+            title => tag.set_title(title),
+        }
+        match self.artist.as_str() {
+            "" => tag.remove_artist(),
+            artist => tag.set_artist(artist),
+        }
+        match self.album.as_str() {
+            "" => tag.remove_album(),
+            album => tag.set_album(album),
+        }
+        if self.genres.is_empty() {
+            tag.remove_genre();
+        } else {
+            tag.set_text_values("TCON", &self.genres);
+        }
+        match self.track {
+            Some(track) => tag.set_track(track),
+            None => tag.remove_track(),
+        }
+
+        tag.write_to_path(path, Version::Id3v24)
+            .map_err(|err| Error::new(err))
+    }
+
+    fn write_native(&self, path: &Path) -> Result<(), Error> {
+        let mut tagged_file = lofty::read_from_path(path)?;
+        if tagged_file.primary_tag().is_none() {
+            tagged_file.insert_tag(lofty::tag::Tag::new(tagged_file.primary_tag_type()));
+        }
+
+        let tag = tagged_file
+            .primary_tag_mut()
+            .ok_or(Error::msg("Writing tags is not supported for this format"))?;
+
+        match self.title.as_str() {
+            "" => tag.remove_title(),
+            title => tag.set_title(title.to_owned()),
+        }
+        match self.artist.as_str() {
+            "" => tag.remove_artist(),
+            artist => tag.set_artist(artist.to_owned()),
+        }
+        match self.album.as_str() {
+            "" => tag.remove_album(),
+            album => tag.set_album(album.to_owned()),
+        }
+        tag.remove_key(ItemKey::Genre);
+        for genre in &self.genres {
+            tag.push(TagItem::new(
+                ItemKey::Genre,
+                ItemValue::Text(genre.to_owned()),
+            ));
+        }
+        match self.track {
+            Some(track) => tag.set_track(track),
+            None => tag.remove_track(),
+        }
+
+        tagged_file
+            .save_to_path(path, WriteOptions::default())
+            .map_err(|err| Error::new(err))
+    }
+}
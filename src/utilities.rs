@@ -1,5 +1,38 @@
+use std::{
+    cmp::Ordering,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 pub fn progress_formatted(seconds: i32) -> String {
     let minutes = seconds / 60;
     let seconds = seconds % 60;
     format!("{:02}:{:02}", minutes, seconds)
 }
+
+/// FNV-1a hash, stable across platforms and compiler versions unlike `DefaultHasher`
+pub fn hash_stable(bytes: &[u8]) -> u64 {
+    hash_stable_from(0xcbf29ce484222325, bytes)
+}
+
+/// Continues `hash_stable` from a previous hash, to hash data read in chunks
+pub fn hash_stable_from(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Compares strings without allocating lowercase copies, which adds up when sorting large libraries
+pub fn cmp_ignore_case(first: &str, second: &str) -> Ordering {
+    first
+        .chars()
+        .flat_map(char::to_lowercase)
+        .cmp(second.chars().flat_map(char::to_lowercase))
+}
+
+/// Seconds since the Unix epoch
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
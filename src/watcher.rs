@@ -0,0 +1,210 @@
+use std::{
+    collections::HashSet,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, Receiver},
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use color_eyre::{Result, eyre::Error};
+use notify::{
+    Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+    event::{AccessKind, AccessMode, ModifyKind},
+};
+
+use crate::{
+    app::{App, NavState},
+    songs::LibraryChanges,
+};
+
+/// How long to wait before trying again to start watching, or to watch directories that failed
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+struct LibraryWatcher {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    /// The configured directories
+    directories: Vec<PathBuf>,
+    /// The directories actually being watched, as others may fail (like a drive that is not mounted)
+    watching: Vec<PathBuf>,
+    last_attempt: Instant,
+    pending: HashSet<PathBuf>,
+    last_event: Instant,
+}
+
+impl LibraryWatcher {
+    fn new() -> Result<LibraryWatcher> {
+        let (sender, receiver) = mpsc::channel();
+
+        Ok(LibraryWatcher {
+            watcher: notify::recommended_watcher(sender)?,
+            receiver,
+            directories: Vec::new(),
+            watching: Vec::new(),
+            last_attempt: Instant::now(),
+            pending: HashSet::new(),
+            last_event: Instant::now(),
+        })
+    }
+
+    /// Watches every given directory, even if some of them (like unmounted drives) fail. Those are
+    /// tried again every so often, but only reported when the directories change
+    fn watch(&mut self, directories: Vec<PathBuf>) -> Result<()> {
+        let changed = self.directories != directories;
+        if changed {
+            for watching in self.watching.drain(..) {
+                let _ = self.watcher.unwatch(&watching);
+            }
+
+            self.pending.clear();
+            self.directories = directories;
+        } else if self.watching.len() == self.directories.len()
+            || self.last_attempt.elapsed() < RETRY_DELAY
+        {
+            return Ok(());
+        }
+
+        self.last_attempt = Instant::now();
+
+        let mut result = Ok(());
+        for directory in &self.directories {
+            if self.watching.contains(directory) {
+                continue;
+            }
+
+            match self.watcher.watch(directory, RecursiveMode::Recursive) {
+                Ok(()) => self.watching.push(directory.clone()),
+                Err(err) if changed => {
+                    result = Err(Error::msg(format!(
+                        "{}: {}",
+                        directory.to_string_lossy(),
+                        err
+                    )));
+                }
+                Err(_) => {}
+            }
+        }
+
+        result
+    }
+
+    fn receive(&mut self) {
+        while let Ok(event) = self.receiver.try_recv() {
+            let Ok(event) = event else {
+                continue;
+            };
+
+            let relevant = match event.kind {
+                EventKind::Create(_) | EventKind::Remove(_) => true,
+                EventKind::Modify(ModifyKind::Metadata(_)) => false,
+                EventKind::Modify(_) => true,
+                EventKind::Access(AccessKind::Close(AccessMode::Write)) => true,
+                _ => false,
+            };
+
+            if relevant && !self.watching.is_empty() {
+                self.pending.extend(event.paths);
+                self.last_event = Instant::now();
+            }
+        }
+    }
+
+    /// Changes are only released once no new events arrived during the debounce window
+    fn take_settled(&mut self, debounce: Duration) -> Option<Vec<PathBuf>> {
+        if self.pending.is_empty() || self.last_event.elapsed() < debounce {
+            None
+        } else {
+            Some(self.pending.drain().collect())
+        }
+    }
+}
+
+pub fn thread_watcher(app: Arc<Mutex<App>>) -> JoinHandle<Result<()>> {
+    thread::spawn(move || {
+        // Only created once watching is turned on, so a failure never affects anyone else. Failures
+        // are reported rather than ending the thread, since that would end the program
+        let mut watcher: Option<LibraryWatcher> = None;
+        let mut failed_at: Option<Instant> = None;
+
+        loop {
+            let state = App::do_once(app.clone(), |app| {
+                if app.nav_state == NavState::Exit {
+                    return None;
+                }
+
+                let directories = if app.config.watch_library {
+                    app.config.music_directories()
+                } else {
+                    Vec::new()
+                };
+
+                Some((
+                    directories,
+                    Duration::from_millis(app.config.watch_debounce_ms),
+                    app.scan.is_some(),
+                ))
+            });
+
+            let Some((directories, debounce, scanning)) = state else {
+                return Ok(());
+            };
+
+            if directories.is_empty() {
+                watcher = None;
+                failed_at = None;
+            } else if watcher.is_none() && failed_at.is_none_or(|at| at.elapsed() >= RETRY_DELAY) {
+                match LibraryWatcher::new() {
+                    Ok(created) => {
+                        watcher = Some(created);
+                        failed_at = None;
+                    }
+                    Err(err) => {
+                        if failed_at.is_none() {
+                            App::do_once(app.clone(), |app| {
+                                app.alert =
+                                    Some(format!("Could not watch music directory: {}", err));
+                                app.needs_redraw = true;
+                            });
+                        }
+                        failed_at = Some(Instant::now());
+                    }
+                }
+            }
+
+            let Some(watcher) = watcher.as_mut() else {
+                thread::sleep(Duration::from_millis(100));
+                continue;
+            };
+
+            if let Err(err) = watcher.watch(directories) {
+                App::do_once(app.clone(), |app| {
+                    app.alert = Some(format!("Could not watch music directory: {}", err));
+                    app.needs_redraw = true;
+                });
+            }
+
+            watcher.receive();
+
+            // Changes made during a scan wait for it, as the scan would replace them with its results
+            if !scanning && let Some(changed) = watcher.take_settled(debounce) {
+                // The files are read without holding the app, so it keeps responding during big copies
+                let mut changes = LibraryChanges::new(&changed);
+                let (config, previous) = App::do_once(app.clone(), |app| {
+                    (app.config.clone(), app.songs.changed_songs(&changes))
+                });
+                changes.probe(&config, previous);
+
+                App::do_once(app.clone(), |app| {
+                    if let Err(err) = app.handle_library_changes(changes) {
+                        app.alert = Some(format!("Could not update music library: {}", err));
+                    }
+                });
+            }
+
+            thread::sleep(Duration::from_millis(100));
+        }
+    })
+}
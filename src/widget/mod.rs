@@ -1,6 +1,14 @@
+pub mod widget_confirm;
+pub mod widget_duplicates;
 pub mod widget_history;
 pub mod widget_library;
+pub mod widget_lyrics;
 pub mod widget_playing;
+pub mod widget_playlists;
 pub mod widget_popup;
+pub mod widget_prompt;
+pub mod widget_report;
+pub mod widget_scan;
 pub mod widget_search;
+pub mod widget_tag_editor;
 pub mod widget_up_next;
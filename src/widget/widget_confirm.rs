@@ -0,0 +1,18 @@
+use ratatui::{
+    layout::Alignment,
+    style::Style,
+    text::Text,
+    widgets::{Block, BorderType, Paragraph},
+};
+
+use crate::app::{App, Confirm};
+
+pub fn build<'a>(app: &App, confirm: &'a Confirm) -> Paragraph<'a> {
+    Paragraph::new(Text::from(confirm.question.as_str()).centered()).block(
+        Block::bordered()
+            .title_bottom(" | [y] Yes | [n] No | ")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Thick)
+            .border_style(Style::default().fg(app.config.color_border)),
+    )
+}
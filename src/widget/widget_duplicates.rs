@@ -0,0 +1,90 @@
+use ratatui::{
+    layout::{Alignment, Constraint},
+    style::{Color, Style, Stylize},
+    widgets::{Block, BorderType, Cell, Row, Table},
+};
+
+use crate::app::App;
+
+pub fn build<'a>(app: &App) -> Table<'a> {
+    let roots = app.config.music_directories();
+    let copies: Vec<Row> = app
+        .duplicates
+        .iter()
+        .enumerate()
+        .flat_map(|(group_index, group)| {
+            group
+                .iter()
+                .enumerate()
+                .filter_map(|(copy, index)| app.songs.song(*index).map(|song| (copy, song)))
+                .map(move |(copy, song)| (group_index, copy, song))
+        })
+        .map(|(group_index, copy, song)| {
+            let path = roots
+                .iter()
+                .find_map(|root| song.path.strip_prefix(root).ok())
+                .unwrap_or(&song.path)
+                .to_string_lossy()
+                .into_owned();
+            let status = if app.songs.is_hidden(song) {
+                "Hidden"
+            } else if copy == 0 {
+                "Best"
+            } else {
+                ""
+            };
+
+            let row = Row::new(vec![
+                Cell::new(format!("{} - {}", song.artists_joined(), song.title)),
+                Cell::new(song.format()),
+                Cell::new(path),
+                Cell::new(status),
+            ]);
+
+            // Groups alternate colors, so that copies of the same song read as one block
+            if group_index % 2 == 0 {
+                row.fg(app.config.color_row)
+            } else {
+                row
+            }
+        })
+        .collect();
+
+    Table::new(
+        copies,
+        [
+            Constraint::Percentage(35),
+            Constraint::Percentage(20),
+            Constraint::Fill(1),
+            Constraint::Length(6),
+        ],
+    )
+    .header(
+        Row::new(vec![
+            Cell::new("Song"),
+            Cell::new("Format"),
+            Cell::new("File"),
+            Cell::new(""),
+        ])
+        .bold()
+        .style(Style::default().fg(app.config.color_headers))
+        .bottom_margin(1),
+    )
+    .row_highlight_style(Style::new().bg(app.config.color_border).fg(Color::Black))
+    .block(
+        Block::bordered()
+            .border_style(Style::new().fg(app.config.color_border))
+            .border_type(BorderType::Thick)
+            .title(match app.hashing {
+                Some(_) => format!(
+                    " Duplicates ({} groups, Comparing Contents...) ",
+                    app.duplicates.len()
+                ),
+                None => format!(" Duplicates ({} groups) ", app.duplicates.len()),
+            })
+            .title_bottom(
+                " | [Esc] Close | [h] Hide/Show | [H] Hide Lesser Copies | [j/k] Up/Down | ",
+            )
+            .title_alignment(Alignment::Center),
+    )
+}
@@ -3,25 +3,54 @@ use std::cmp::max;
 use ratatui::{
     layout::{Constraint, Rect},
     style::{Color, Style, Stylize},
-    widgets::{Cell, Row, Table},
+    widgets::{Cell, Row, Table, TableState},
 };
 use textwrap::Options;
 
 use crate::app::App;
 
-pub fn build<'a>(app: &App, area: Rect) -> Table<'a> {
-    let left_percent = 0.66;
-    let right_percent = 0.33;
-    let row_constraints = [
+/// Only the rows that can be visible are built, so the library state is translated to a state relative
+/// to the first built row. Returns where that window starts and the translated state
+pub fn window(state: &TableState, area: Rect) -> (usize, TableState) {
+    let height = area.height as usize;
+    let mut start = state.offset();
+
+    if let Some(selected) = state.selected() {
+        if selected < start {
+            start = selected;
+        } else if selected >= start + height {
+            start = selected + 1 - height;
+        }
+    }
+
+    let window_state = TableState::default()
+        .with_offset(0)
+        .with_selected(state.selected().map(|selected| selected - start));
+    (start, window_state)
+}
+
+pub fn build<'a>(app: &App, area: Rect, start: usize) -> Table<'a> {
+    // Only show where songs come from when there is more than one library root
+    let show_root = app.config.music_directories().len() > 1;
+    let (left_percent, right_percent, root_percent) = if show_root {
+        (0.5, 0.3, 0.2)
+    } else {
+        (0.66, 0.33, 0.0)
+    };
+    let mut row_constraints = vec![
         Constraint::Percentage((left_percent * 100.0) as u16),
         Constraint::Percentage((right_percent * 100.0) as u16),
     ];
+    if show_root {
+        row_constraints.push(Constraint::Percentage((root_percent * 100.0) as u16));
+    }
 
     let next: Vec<Row> = app
         .songs
         .showing_songs_library()
-        .iter()
         .enumerate()
+        .skip(start)
+        .take(area.height as usize)
         .map(|(i, song)| {
             let title_width = (left_percent * area.width as f32) as u16 - 1;
             let options = Options::new(title_width as usize).break_words(true);
@@ -32,35 +61,53 @@ pub fn build<'a>(app: &App, area: Rect) -> Table<'a> {
                 .collect();
 
             let artist_width = (right_percent * area.width as f32) as u16 - 1;
-            let artist_lines = textwrap::wrap(&song.artist, artist_width as usize);
+            let artists = song.artists_joined();
+            let artist_lines = textwrap::wrap(&artists, artist_width as usize);
             let artist_lines_str: String = artist_lines
                 .iter()
                 .map(|line| format!("{}\n", line))
                 .collect();
 
+            let mut cells = vec![Cell::new(title_lines_str), Cell::new(artist_lines_str)];
+            let mut height = max(title_lines.len(), artist_lines.len());
+
+            if show_root {
+                let root_width = (root_percent * area.width as f32) as u16 - 1;
+                let root_name = song
+                    .root
+                    .file_name()
+                    .map(|name| name.to_string_lossy())
+                    .unwrap_or(song.root.to_string_lossy());
+                let root_lines = textwrap::wrap(&root_name, root_width as usize);
+                let root_lines_str: String = root_lines
+                    .iter()
+                    .map(|line| format!("{}\n", line))
+                    .collect();
+
+                height = max(height, root_lines.len());
+                cells.push(Cell::new(root_lines_str));
+            }
+
             if i % 2 == 0 {
-                Row::new(vec![
-                    Cell::new(title_lines_str),
-                    Cell::new(artist_lines_str),
-                ])
-                .height(max(title_lines.len(), artist_lines.len()) as u16)
-                .fg(app.config.color_row)
+                Row::new(cells)
+                    .height(height as u16)
+                    .fg(app.config.color_row)
             } else {
-                Row::new(vec![
-                    Cell::new(title_lines_str),
-                    Cell::new(artist_lines_str),
-                ])
-                .height(max(title_lines.len(), artist_lines.len()) as u16)
+                Row::new(cells).height(height as u16)
             }
         })
         .collect();
 
     Table::new(next, row_constraints)
         .header(
-            Row::new(vec![Cell::new("Title"), Cell::new("Artist")])
-                .bold()
-                .style(Style::default().fg(app.config.color_headers))
-                .bottom_margin(1),
+            Row::new(if show_root {
+                vec![Cell::new("Title"), Cell::new("Artist"), Cell::new("Root")]
+            } else {
+                vec![Cell::new("Title"), Cell::new("Artist")]
+            })
+            .bold()
+            .style(Style::default().fg(app.config.color_headers))
+            .bottom_margin(1),
         )
         .row_highlight_style(Style::new().bg(app.config.color_border).fg(Color::Black))
 }
@@ -0,0 +1,61 @@
+use ratatui::{
+    layout::{Alignment, Constraint, Rect},
+    style::{Color, Style, Stylize},
+    widgets::{Block, BorderType, Cell, Row, Table},
+};
+
+use crate::app::App;
+
+pub fn build<'a>(app: &App, area: Rect, current: Option<usize>) -> Table<'a> {
+    let width = area.width.saturating_sub(4).max(1) as usize;
+
+    let rows: Vec<Row> = match &app.lyrics {
+        Some(lyrics) => lyrics
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                let lines = textwrap::wrap(&line.text, width);
+                let text: String = lines.iter().map(|line| format!("{}\n", line)).collect();
+                let row = Row::new(vec![Cell::new(text)]).height(lines.len().max(1) as u16);
+
+                if Some(i) == current {
+                    row.bold().fg(app.config.color_headers)
+                } else if current.is_some_and(|current| i < current) {
+                    row.fg(app.config.color_row)
+                } else {
+                    row
+                }
+            })
+            .collect(),
+        None if app.songs.current_song().is_some() => {
+            vec![Row::new(vec![Cell::new("No lyrics found for this song")])]
+        }
+        None => vec![Row::new(vec![Cell::new("No Track Loaded")])],
+    };
+
+    let title = match app.songs.current_song() {
+        Some(song) => format!(" Lyrics: {} - {} ", song.artists_joined(), song.title),
+        None => " Lyrics ".to_owned(),
+    };
+    let title_bottom = if app.lyrics.as_ref().is_some_and(|lyrics| lyrics.synced) {
+        " | [Esc] Close | [j/k] Scroll | [f] Follow Song | "
+    } else {
+        " | [Esc] Close | [j/k] Scroll | "
+    };
+
+    Table::new(rows, [Constraint::Fill(1)])
+        .row_highlight_style(if app.lyrics_follow {
+            Style::new()
+        } else {
+            Style::new().bg(app.config.color_border).fg(Color::Black)
+        })
+        .block(
+            Block::bordered()
+                .border_style(Style::new().fg(app.config.color_border))
+                .border_type(BorderType::Thick)
+                .title(title)
+                .title_bottom(title_bottom)
+                .title_alignment(Alignment::Center),
+        )
+}
@@ -19,7 +19,14 @@ pub fn build<'a>(app: &App) -> (Paragraph<'a>, Block<'a>) {
                 ]),
                 Line::from(vec![
                     Span::styled("Artist: ", Style::default().fg(app.config.color_headers)),
-                    Span::raw(playing.artist.to_owned()),
+                    Span::raw(playing.artists_joined()),
+                ]),
+                Line::from(vec![
+                    Span::styled("Album: ", Style::default().fg(app.config.color_headers)),
+                    Span::raw(match playing.year {
+                        Some(year) => format!("{} ({})", playing.album, year),
+                        None => playing.album.to_owned(),
+                    }),
                 ]),
                 Line::from(vec![
                     Span::styled("Genre: ", Style::default().fg(app.config.color_headers)),
@@ -33,6 +40,10 @@ pub fn build<'a>(app: &App) -> (Paragraph<'a>, Block<'a>) {
                             .join(", "),
                     ),
                 ]),
+                Line::from(vec![
+                    Span::styled("Format: ", Style::default().fg(app.config.color_headers)),
+                    Span::raw(playing.format()),
+                ]),
             ])
         } else {
             Paragraph::new(Line::from("No Track Loaded")).centered()
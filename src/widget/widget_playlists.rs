@@ -0,0 +1,40 @@
+use ratatui::{
+    layout::Constraint,
+    style::{Color, Style, Stylize},
+    widgets::{Cell, Row, Table},
+};
+
+use crate::App;
+
+pub fn build<'a>(app: &App) -> Table<'a> {
+    let rows: Vec<Row> = app
+        .playlists
+        .iter()
+        .enumerate()
+        .map(|(i, playlist)| {
+            let name = match &playlist.smart {
+                Some(smart) => format!("{} (Smart: {})", playlist.name, smart.describe()),
+                None => playlist.name.clone(),
+            };
+            let row = Row::new(vec![
+                Cell::new(name),
+                Cell::new(playlist.paths.len().to_string()),
+            ]);
+
+            if i % 2 == 0 {
+                row.fg(app.config.color_row)
+            } else {
+                row
+            }
+        })
+        .collect();
+
+    Table::new(rows, [Constraint::Fill(1), Constraint::Length(6)])
+        .header(
+            Row::new(vec![Cell::new("Name"), Cell::new("Songs")])
+                .bold()
+                .style(Style::default().fg(app.config.color_headers))
+                .bottom_margin(1),
+        )
+        .row_highlight_style(Style::new().bg(app.config.color_border).fg(Color::Black))
+}
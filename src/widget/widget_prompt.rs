@@ -0,0 +1,19 @@
+use ratatui::{
+    layout::Alignment,
+    style::Style,
+    text::Text,
+    widgets::{Block, BorderType, Paragraph},
+};
+
+use crate::app::{App, Prompt};
+
+pub fn build<'a>(app: &App, prompt: &'a Prompt) -> Paragraph<'a> {
+    Paragraph::new(Text::from(format!("{}_", prompt.text))).block(
+        Block::bordered()
+            .title_top(prompt.kind.title())
+            .title_bottom(" | [Enter] Save | [Esc] Cancel | ")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Thick)
+            .border_style(Style::default().fg(app.config.color_border)),
+    )
+}
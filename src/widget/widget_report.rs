@@ -0,0 +1,77 @@
+use std::cmp::max;
+
+use ratatui::{
+    layout::{Alignment, Constraint, Rect},
+    style::{Color, Style, Stylize},
+    widgets::{Block, BorderType, Cell, Row, Table},
+};
+use textwrap::Options;
+
+use crate::app::App;
+
+pub fn build<'a>(app: &App, area: Rect) -> Table<'a> {
+    let left_percent = 0.66;
+    let right_percent = 0.33;
+    let row_constraints = [
+        Constraint::Percentage((left_percent * 100.0) as u16),
+        Constraint::Percentage((right_percent * 100.0) as u16),
+    ];
+
+    let roots = app.config.music_directories();
+    let failures: Vec<Row> = app
+        .songs
+        .report
+        .failures
+        .iter()
+        .enumerate()
+        .map(|(i, failure)| {
+            let path = roots
+                .iter()
+                .find_map(|root| failure.path.strip_prefix(root).ok())
+                .filter(|path| !path.as_os_str().is_empty())
+                .unwrap_or(&failure.path)
+                .to_string_lossy();
+
+            let path_width = (left_percent * area.width as f32) as u16 - 1;
+            let options = Options::new(path_width as usize).break_words(true);
+            let path_lines = textwrap::wrap(&path, options);
+            let path_lines_str: String = path_lines
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect();
+
+            let reason_width = (right_percent * area.width as f32) as u16 - 1;
+            let reason_lines = textwrap::wrap(&failure.reason, reason_width as usize);
+            let reason_lines_str: String = reason_lines
+                .iter()
+                .map(|line| format!("{}\n", line))
+                .collect();
+
+            let row = Row::new(vec![Cell::new(path_lines_str), Cell::new(reason_lines_str)])
+                .height(max(path_lines.len(), reason_lines.len()) as u16);
+
+            if i % 2 == 0 {
+                row.fg(app.config.color_row)
+            } else {
+                row
+            }
+        })
+        .collect();
+
+    Table::new(failures, row_constraints)
+        .header(
+            Row::new(vec![Cell::new("File"), Cell::new("Reason")])
+                .bold()
+                .style(Style::default().fg(app.config.color_headers))
+                .bottom_margin(1),
+        )
+        .row_highlight_style(Style::new().bg(app.config.color_border).fg(Color::Black))
+        .block(
+            Block::bordered()
+                .border_style(Style::new().fg(app.config.color_border))
+                .border_type(BorderType::Thick)
+                .title(" Scan Report ")
+                .title_bottom(" | [Esc] Close | [j/k] Up/Down | ")
+                .title_alignment(Alignment::Center),
+        )
+}
@@ -0,0 +1,44 @@
+use std::sync::atomic::Ordering;
+
+use ratatui::{
+    layout::Alignment,
+    style::Style,
+    text::{Line, Span},
+    widgets::{Block, BorderType, Paragraph},
+};
+
+use crate::{app::App, scan::ScanProgress, utilities::progress_formatted};
+
+pub fn build<'a>(app: &App, progress: &ScanProgress) -> Paragraph<'a> {
+    let found = progress.found.load(Ordering::Relaxed);
+    let probed = progress.probed.load(Ordering::Relaxed);
+    let failed = progress.failed.load(Ordering::Relaxed);
+
+    let percent = (probed.min(found) * 100).checked_div(found).unwrap_or(0);
+    let eta = match progress.eta() {
+        Some(eta) => format!("~{}", progress_formatted(eta.as_secs() as i32)),
+        None => "Estimating...".to_owned(),
+    };
+
+    let line = |header: &'a str, value: String| {
+        Line::from(vec![
+            Span::styled(header, Style::default().fg(app.config.color_headers)),
+            Span::raw(value),
+        ])
+    };
+
+    Paragraph::new(vec![
+        line("Found: ", format!("{} files", found)),
+        line("Read: ", format!("{} / {} ({}%)", probed, found, percent)),
+        line("Errors: ", failed.to_string()),
+        line("Time Left: ", eta),
+        Line::from("Songs can be browsed while the rest are read"),
+    ])
+    .block(
+        Block::bordered()
+            .border_style(Style::new().fg(app.config.color_border))
+            .border_type(BorderType::Thick)
+            .title(" Scanning Library ")
+            .title_alignment(Alignment::Center),
+    )
+}
@@ -0,0 +1,37 @@
+use ratatui::{
+    layout::Alignment,
+    style::{Style, Stylize},
+    text::{Line, Span, Text},
+    widgets::{Block, BorderType, Paragraph},
+};
+
+use crate::app::{App, TagEditor};
+
+pub fn build<'a>(app: &App, editor: &'a TagEditor) -> Paragraph<'a> {
+    let lines: Vec<Line> = TagEditor::LABELS
+        .iter()
+        .zip(editor.fields.iter())
+        .enumerate()
+        .map(|(i, (label, value))| {
+            let label = Span::from(format!("{:<8}", format!("{}:", label)))
+                .style(Style::default().fg(app.config.color_headers));
+            if i == editor.focused {
+                Line::from(vec![label, Span::from(format!("{}_", value)).bold()])
+            } else {
+                Line::from(vec![
+                    label,
+                    Span::from(value.as_str()).style(Style::default().fg(app.config.color_row)),
+                ])
+            }
+        })
+        .collect();
+
+    Paragraph::new(Text::from(lines)).block(
+        Block::bordered()
+            .title_top(" Edit Tags ")
+            .title_bottom(" | [Tab] Next Field | [Enter] Save | [Esc] Cancel | ")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Thick)
+            .border_style(Style::default().fg(app.config.color_border)),
+    )
+}
@@ -32,7 +32,8 @@ pub fn build<'a>(app: &App, area: Rect) -> Table<'a> {
                 .collect();
 
             let artist_width = (right_percent * area.width as f32) as u16 - 1;
-            let artist_lines = textwrap::wrap(&song.artist, artist_width as usize);
+            let artists = song.artists_joined();
+            let artist_lines = textwrap::wrap(&artists, artist_width as usize);
             let artist_lines_str: String = artist_lines
                 .iter()
                 .map(|line| format!("{}\n", line))